@@ -66,10 +66,7 @@ fn add_steam_network(app: &mut App) {
 
     let server: RenetServer = RenetServer::new(connection_config());
 
-    let steam_transport_config = SteamServerConfig {
-        max_clients: 10,
-        access_permission: AccessPermission::Public,
-    };
+    let steam_transport_config = SteamServerConfig::new(10, AccessPermission::Public);
     let transport = SteamServerTransport::new(&steam_client, steam_transport_config).unwrap();
 
     app.add_plugins(SteamServerPlugin);
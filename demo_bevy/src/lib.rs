@@ -81,6 +81,8 @@ impl ClientChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::ZERO,
                 },
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
             ChannelConfig {
                 channel_id: Self::Command.into(),
@@ -88,6 +90,8 @@ impl ClientChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::ZERO,
                 },
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
         ]
     }
@@ -109,6 +113,8 @@ impl ServerChannel {
                 channel_id: Self::NetworkedEntities.into(),
                 max_memory_usage_bytes: 10 * 1024 * 1024,
                 send_type: SendType::Unreliable,
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
             ChannelConfig {
                 channel_id: Self::ServerMessages.into(),
@@ -116,6 +122,8 @@ impl ServerChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::from_millis(200),
                 },
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
         ]
     }
@@ -126,6 +134,10 @@ pub fn connection_config() -> ConnectionConfig {
         available_bytes_per_tick: 1024 * 1024,
         client_channels_config: ClientChannel::channels_config(),
         server_channels_config: ServerChannel::channels_config(),
+        global_ordering: false,
+        time_sync_interval: None,
+        validate_config: false,
+        max_retransmits: None,
     }
 }
 
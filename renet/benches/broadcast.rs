@@ -0,0 +1,55 @@
+//! Compares broadcasting a large reliable payload with a shared buffer against
+//! serializing it for each client, run with `cargo bench -p renet --bench broadcast`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use renet::{Bytes, ClientId, ConnectionConfig, DefaultChannel, RenetServer};
+
+const CLIENTS: u64 = 64;
+const PAYLOAD_SIZE: usize = 50 * 1024;
+const ITERATIONS: u32 = 50;
+
+fn new_server() -> RenetServer {
+    let config = ConnectionConfig {
+        available_bytes_per_tick: u64::MAX,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config);
+    for client_id in 0..CLIENTS {
+        server.add_connection(client_id);
+    }
+    server
+}
+
+fn send_packets(server: &mut RenetServer) {
+    for client_id in 0..CLIENTS {
+        black_box(server.get_packets_to_send(client_id as ClientId).unwrap());
+    }
+}
+
+fn bench(name: &str, send: impl Fn(&mut RenetServer, &[u8])) {
+    let payload = vec![7u8; PAYLOAD_SIZE];
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        // New connections so the reliable channels do not fill up with unacked messages
+        let mut server = new_server();
+        let start = Instant::now();
+        send(&mut server, &payload);
+        send_packets(&mut server);
+        total += start.elapsed();
+    }
+
+    println!("{name}: {:?} per broadcast", total / ITERATIONS);
+}
+
+fn main() {
+    bench("per client serialize", |server, payload| {
+        for client_id in 0..CLIENTS {
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, payload.to_vec());
+        }
+    });
+
+    bench("shared broadcast", |server, payload| {
+        server.broadcast_message(DefaultChannel::ReliableOrdered, Bytes::copy_from_slice(payload));
+    });
+}
@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use octets::Octets;
+
+use crate::error::{ChannelError, ImportStateError};
+use crate::state;
+
+/// Orders the messages of all reliable channels of a connection relative to each other.
+/// Each message is prefixed with a sequence shared by all channels, received messages
+/// wait in a reorder buffer until all the previous ones were received.
+#[derive(Debug)]
+pub struct GlobalOrder {
+    max_memory_usage_bytes: usize,
+    memory_usage_bytes: usize,
+    next_send_sequence: u64,
+    next_receive_sequence: u64,
+    pending: BTreeMap<u64, (u8, Bytes)>,
+    ready: HashMap<u8, VecDeque<Bytes>>,
+}
+
+impl GlobalOrder {
+    pub fn new(max_memory_usage_bytes: usize) -> Self {
+        Self {
+            max_memory_usage_bytes,
+            memory_usage_bytes: 0,
+            next_send_sequence: 0,
+            next_receive_sequence: 0,
+            pending: BTreeMap::new(),
+            ready: HashMap::new(),
+        }
+    }
+
+    /// Returns the message with the next send sequence, call [`Self::sent`] if the message was sent.
+    pub fn wrap(&self, message: Bytes) -> Bytes {
+        let sequence_len = octets::varint_len(self.next_send_sequence);
+        let mut buffer = vec![0u8; sequence_len];
+        octets::OctetsMut::with_slice(&mut buffer)
+            .put_varint(self.next_send_sequence)
+            .expect("buffer has the varint length");
+
+        let mut wrapped = BytesMut::with_capacity(sequence_len + message.len());
+        wrapped.put_slice(&buffer);
+        wrapped.put_slice(&message);
+        wrapped.freeze()
+    }
+
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn sent(&mut self) {
+        self.next_send_sequence += 1;
+    }
+
+    pub fn process_message(&mut self, channel_id: u8, message: Bytes) -> Result<(), ChannelError> {
+        let mut octets = octets::Octets::with_slice(&message);
+        let sequence = octets.get_varint().map_err(|_| ChannelError::InvalidGlobalSequence)?;
+        let payload = message.slice(octets.off()..);
+
+        if sequence < self.next_receive_sequence {
+            // Discard message already received
+            return Ok(());
+        }
+
+        if self.memory_usage_bytes + payload.len() > self.max_memory_usage_bytes {
+            return Err(ChannelError::ReliableChannelMaxMemoryReached);
+        }
+        self.memory_usage_bytes += payload.len();
+        if let Some((_, old)) = self.pending.insert(sequence, (channel_id, payload)) {
+            self.memory_usage_bytes -= old.len();
+        }
+
+        while let Some((channel_id, payload)) = self.pending.remove(&self.next_receive_sequence) {
+            self.ready.entry(channel_id).or_default().push_back(payload);
+            self.next_receive_sequence += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn receive_message(&mut self, channel_id: u8) -> Option<Bytes> {
+        let message = self.ready.get_mut(&channel_id)?.pop_front()?;
+        self.memory_usage_bytes -= message.len();
+        Some(message)
+    }
+
+    pub fn export_state(&self, buffer: &mut Vec<u8>) {
+        buffer.put_u64(self.next_send_sequence);
+        buffer.put_u64(self.next_receive_sequence);
+        buffer.put_u64(self.pending.len() as u64);
+        for (&sequence, (channel_id, message)) in self.pending.iter() {
+            buffer.put_u64(sequence);
+            buffer.put_u8(*channel_id);
+            state::put_bytes(buffer, message);
+        }
+
+        buffer.put_u64(self.ready.len() as u64);
+        for (&channel_id, messages) in self.ready.iter() {
+            buffer.put_u8(channel_id);
+            buffer.put_u64(messages.len() as u64);
+            for message in messages.iter() {
+                state::put_bytes(buffer, message);
+            }
+        }
+    }
+
+    pub fn import_state(&mut self, octets: &mut Octets) -> Result<(), ImportStateError> {
+        self.next_send_sequence = octets.get_u64()?;
+        self.next_receive_sequence = octets.get_u64()?;
+        for _ in 0..state::get_len(octets)? {
+            let sequence = octets.get_u64()?;
+            let channel_id = octets.get_u8()?;
+            let message = state::get_bytes(octets)?;
+            self.memory_usage_bytes += message.len();
+            self.pending.insert(sequence, (channel_id, message));
+        }
+
+        for _ in 0..state::get_len(octets)? {
+            let channel_id = octets.get_u8()?;
+            let messages = self.ready.entry(channel_id).or_default();
+            for _ in 0..state::get_len(octets)? {
+                let message = state::get_bytes(octets)?;
+                self.memory_usage_bytes += message.len();
+                messages.push_back(message);
+            }
+        }
+
+        if self.memory_usage_bytes > self.max_memory_usage_bytes {
+            return Err(ImportStateError::ConfigMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deliver_in_global_order() {
+        let mut sender = GlobalOrder::new(1024);
+        let mut receiver = GlobalOrder::new(1024);
+
+        let first = sender.wrap(Bytes::from("first"));
+        sender.sent();
+        let second = sender.wrap(Bytes::from("second"));
+        sender.sent();
+
+        // The second message arrives first on another channel
+        receiver.process_message(2, second).unwrap();
+        assert_eq!(receiver.receive_message(2), None);
+
+        receiver.process_message(1, first.clone()).unwrap();
+        assert_eq!(receiver.receive_message(1).unwrap(), "first");
+        assert_eq!(receiver.receive_message(2).unwrap(), "second");
+
+        // Duplicates are discarded
+        receiver.process_message(1, first).unwrap();
+        assert_eq!(receiver.receive_message(1), None);
+    }
+
+    #[test]
+    fn invalid_sequence() {
+        let mut receiver = GlobalOrder::new(1024);
+        let result = receiver.process_message(0, Bytes::new());
+        assert_eq!(result, Err(ChannelError::InvalidGlobalSequence));
+    }
+
+    #[test]
+    fn max_memory_usage() {
+        let sender = GlobalOrder::new(10);
+        let mut receiver = GlobalOrder::new(10);
+
+        // Message waiting for a previous sequence that never arrives
+        let mut sender_ahead = GlobalOrder::new(10);
+        sender_ahead.sent();
+        receiver.process_message(0, sender_ahead.wrap(Bytes::from(vec![0; 8]))).unwrap();
+
+        let result = receiver.process_message(0, sender.wrap(Bytes::from(vec![0; 8])));
+        assert_eq!(result, Err(ChannelError::ReliableChannelMaxMemoryReached));
+    }
+}
@@ -1,9 +1,12 @@
+pub(crate) mod global_order;
 pub(crate) mod reliable;
 pub(crate) mod slice_constructor;
 pub(crate) mod unreliable;
 
 use std::time::Duration;
 
+use bytes::Bytes;
+
 pub(crate) use slice_constructor::SliceConstructor;
 
 /// Delivery guarantee of a channel
@@ -19,6 +22,23 @@ pub enum SendType {
     ReliableUnordered {
         resend_time: Duration,
     },
+    /// Messages are resent until acknowledged or until the deadline after they were sent,
+    /// then they are given up and reported with [`RenetClient::receive_expired_message`][crate::RenetClient::receive_expired_message].
+    /// Received messages are in the same order they were sent, expired ones are skipped.
+    /// An expired message could still have been received if its acknowledgement was lost or late.
+    ReliableWithDeadline {
+        resend_time: Duration,
+        deadline: Duration,
+    },
+}
+
+/// A message from a [`SendType::ReliableWithDeadline`] channel that was not acknowledged before the deadline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredMessage {
+    pub channel_id: u8,
+    /// Index of the message in the channel, the first message sent in the channel has id 0.
+    pub message_id: u64,
+    pub message: Bytes,
 }
 
 /// Configuration of a channel for a server or client
@@ -34,6 +54,37 @@ pub struct ChannelConfig {
     pub max_memory_usage_bytes: usize,
     /// Delivery guarantee of the channel.
     pub send_type: SendType,
+    /// Maximum number of reliable messages waiting for acknowledgement in the channel, no limit when None.
+    /// When reached, [`RenetClient::try_send_message`][crate::RenetClient::try_send_message] and
+    /// [`RenetServer::try_send_message`][crate::RenetServer::try_send_message] return a [`ChannelFull`][crate::ChannelFull] error
+    /// and the message is not sent.
+    /// Ignored by unreliable channels.
+    ///
+    /// <p style="background:rgba(255,181,77,0.16);padding:0.5em;">
+    /// <strong>Warning:</strong> like <code>max_memory_usage_bytes</code>, reaching the limit with <code>send_message</code>
+    /// disconnects the client with <code>SendChannelError</code>. Only send on channels with a limit through <code>try_send_message</code>.
+    /// </p>
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ChannelFull, RenetClient, ConnectionConfig};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// # let rpc_channel = 1;
+    /// # let pending_rpcs: Vec<Vec<u8>> = vec![];
+    /// for rpc in pending_rpcs {
+    ///     if let Err(ChannelFull { channel_id }) = client.try_send_message(rpc_channel, rpc) {
+    ///         // Keep the remaining rpcs and retry once the channel acknowledged some messages
+    ///         println!("Channel {channel_id} is full");
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    pub max_in_flight: Option<usize>,
+    /// Maximum number of messages queued in an unreliable channel between packet generations, no limit when None.
+    /// When reached, the oldest queued message is dropped to make room for the new one,
+    /// see [`RenetClient::dropped_unreliable_messages`][crate::RenetClient::dropped_unreliable_messages].
+    /// Ignored by reliable channels.
+    pub max_unreliable_queue: Option<usize>,
 }
 
 /// Utility enumerator when using the default channels configuration.
@@ -61,6 +112,8 @@ impl DefaultChannel {
                 channel_id: 0,
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::Unreliable,
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
             ChannelConfig {
                 channel_id: 1,
@@ -68,6 +121,8 @@ impl DefaultChannel {
                 send_type: SendType::ReliableUnordered {
                     resend_time: Duration::from_millis(300),
                 },
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
             ChannelConfig {
                 channel_id: 2,
@@ -75,6 +130,8 @@ impl DefaultChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::from_millis(300),
                 },
+                max_in_flight: None,
+                max_unreliable_queue: None,
             },
         ]
     }
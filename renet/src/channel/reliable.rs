@@ -1,21 +1,29 @@
 use std::{
-    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap, VecDeque},
     time::Duration,
 };
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
+use octets::Octets;
 
 use super::SliceConstructor;
 use crate::{
-    error::ChannelError,
+    error::{ChannelError, ImportStateError},
     packet::{Packet, Slice, SLICE_SIZE},
+    state,
 };
 
+// Messages in channels with a deadline are prefixed to tell apart the ones that expired,
+// expired messages are replaced by a small message with just the prefix so the receiver can skip them.
+const DEADLINE_MESSAGE: u8 = 0;
+const DEADLINE_EXPIRED: u8 = 1;
+
 #[derive(Debug)]
 enum UnackedMessage {
     Small {
         message: Bytes,
         last_sent: Option<Duration>,
+        retransmits: u32,
     },
     Sliced {
         message: Bytes,
@@ -24,6 +32,7 @@ enum UnackedMessage {
         next_slice_to_send: usize,
         acked: Vec<bool>,
         last_sent: Vec<Option<Duration>>,
+        retransmits: Vec<u32>,
     },
 }
 
@@ -35,6 +44,18 @@ pub struct SendChannelReliable {
     resend_time: Duration,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    max_in_flight: Option<usize>,
+    deadline: Option<Duration>,
+    current_time: Duration,
+    // Message ids with the time they expire, in the order they were sent
+    expirations: VecDeque<(u64, Duration)>,
+    max_retransmits: Option<u32>,
+    retransmits: u64,
+    // Set when a message or slice reached max_retransmits without being acked
+    retransmit_limit_reached: bool,
+    // Spans of labeled messages, closed when the message is acked
+    #[cfg(feature = "tracing")]
+    labels: HashMap<u64, tracing::Span>,
 }
 
 #[derive(Debug)]
@@ -54,6 +75,9 @@ pub struct ReceiveChannelReliable {
     reliable_order: ReliableOrder,
     memory_usage_bytes: usize,
     max_memory_usage_bytes: usize,
+    deadline: bool,
+    #[cfg(feature = "frame_log")]
+    arrived: Vec<Bytes>,
 }
 
 impl UnackedMessage {
@@ -67,12 +91,13 @@ impl UnackedMessage {
             next_slice_to_send: 0,
             acked: vec![false; num_slices],
             last_sent: vec![None; num_slices],
+            retransmits: vec![0; num_slices],
         }
     }
 }
 
 impl SendChannelReliable {
-    pub fn new(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize, max_in_flight: Option<usize>) -> Self {
         Self {
             channel_id,
             unacked_messages: BTreeMap::new(),
@@ -80,15 +105,93 @@ impl SendChannelReliable {
             resend_time,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            max_in_flight,
+            deadline: None,
+            current_time: Duration::ZERO,
+            expirations: VecDeque::new(),
+            max_retransmits: None,
+            retransmits: 0,
+            retransmit_limit_reached: false,
+            #[cfg(feature = "tracing")]
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Messages not acknowledged after the deadline are no longer resent, see [`Self::update`].
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn has_deadline(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    /// Messages or slices are not resent more than `max_retransmits` times, see [`Self::retransmit_limit_reached`].
+    pub fn with_max_retransmits(mut self, max_retransmits: Option<u32>) -> Self {
+        self.max_retransmits = max_retransmits;
+        self
+    }
+
+    /// Returns true if a message was not acknowledged after being resent `max_retransmits` times,
+    /// the connection should be considered dead.
+    pub fn retransmit_limit_reached(&self) -> bool {
+        self.retransmit_limit_reached
+    }
+
+    /// Returns the total number of times messages and slices were resent.
+    pub fn retransmits(&self) -> u64 {
+        self.retransmits
+    }
+
+    /// Expires the messages that passed the deadline, returns their ids and payloads.
+    pub fn update(&mut self, current_time: Duration) -> Vec<(u64, Bytes)> {
+        self.current_time = current_time;
+
+        let mut expired = vec![];
+        while let Some(&(message_id, expires_at)) = self.expirations.front() {
+            if current_time < expires_at {
+                break;
+            }
+            self.expirations.pop_front();
+
+            let Some(unacked_message) = self.unacked_messages.get_mut(&message_id) else {
+                // Already acked
+                continue;
+            };
+
+            let tombstone = UnackedMessage::Small {
+                message: Bytes::from_static(&[DEADLINE_EXPIRED]),
+                last_sent: None,
+                retransmits: 0,
+            };
+            let (UnackedMessage::Small { message, .. } | UnackedMessage::Sliced { message, .. }) =
+                std::mem::replace(unacked_message, tombstone);
+            self.memory_usage_bytes = self.memory_usage_bytes - message.len() + 1;
+            expired.push((message_id, message.slice(1..)));
         }
+
+        expired
+    }
+
+    fn is_full(&self) -> bool {
+        matches!(self.max_in_flight, Some(max_in_flight) if self.unacked_messages.len() >= max_in_flight)
     }
 
     pub fn available_memory(&self) -> usize {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
+    pub fn has_pending_data(&self) -> bool {
+        !self.unacked_messages.is_empty()
+    }
+
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
-        size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
+        !self.is_full() && size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
 
     pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64, current_time: Duration) -> Vec<Packet> {
@@ -103,7 +206,11 @@ impl SendChannelReliable {
 
         'messages: for (&message_id, unacked_message) in self.unacked_messages.iter_mut() {
             match unacked_message {
-                UnackedMessage::Small { message, last_sent } => {
+                UnackedMessage::Small {
+                    message,
+                    last_sent,
+                    retransmits,
+                } => {
                     if *available_bytes < message.len() as u64 {
                         // Skip message, no bytes available to send this message
                         continue;
@@ -113,6 +220,12 @@ impl SendChannelReliable {
                         if current_time - *last_sent < self.resend_time {
                             continue;
                         }
+                        if matches!(self.max_retransmits, Some(max) if *retransmits >= max) {
+                            self.retransmit_limit_reached = true;
+                            continue;
+                        }
+                        *retransmits += 1;
+                        self.retransmits += 1;
                     }
 
                     *available_bytes -= message.len() as u64;
@@ -132,6 +245,10 @@ impl SendChannelReliable {
                     small_messages_bytes += serialized_size;
                     small_messages.push((message_id, message.clone()));
                     *last_sent = Some(current_time);
+                    #[cfg(feature = "tracing")]
+                    if let Some(span) = self.labels.get(&message_id) {
+                        span.in_scope(|| tracing::debug!(sequence = *packet_sequence, "sent"));
+                    }
 
                     continue;
                 }
@@ -141,6 +258,7 @@ impl SendChannelReliable {
                     acked,
                     last_sent,
                     next_slice_to_send,
+                    retransmits,
                     ..
                 } => {
                     let start_index = *next_slice_to_send;
@@ -159,6 +277,12 @@ impl SendChannelReliable {
                             if current_time - last_sent < self.resend_time {
                                 continue;
                             }
+                            if matches!(self.max_retransmits, Some(max) if retransmits[i] >= max) {
+                                self.retransmit_limit_reached = true;
+                                continue;
+                            }
+                            retransmits[i] += 1;
+                            self.retransmits += 1;
                         }
 
                         let start = i * SLICE_SIZE;
@@ -179,6 +303,10 @@ impl SendChannelReliable {
                             channel_id: self.channel_id,
                             slice,
                         });
+                        #[cfg(feature = "tracing")]
+                        if let Some(span) = self.labels.get(&message_id) {
+                            span.in_scope(|| tracing::debug!(sequence = *packet_sequence, slice_index = i, "sent"));
+                        }
 
                         *packet_sequence += 1;
                         last_sent[i] = Some(current_time);
@@ -202,23 +330,66 @@ impl SendChannelReliable {
     }
 
     pub fn send_message(&mut self, message: Bytes) -> Result<(), ChannelError> {
+        let message = match self.deadline {
+            Some(_) => {
+                let mut prefixed = BytesMut::with_capacity(message.len() + 1);
+                prefixed.put_u8(DEADLINE_MESSAGE);
+                prefixed.put_slice(&message);
+                prefixed.freeze()
+            }
+            None => message,
+        };
+
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
             return Err(ChannelError::ReliableChannelMaxMemoryReached);
         }
 
+        if self.is_full() {
+            return Err(ChannelError::ReliableChannelMaxInFlightReached);
+        }
+
         self.memory_usage_bytes += message.len();
         let unacked_message = if message.len() > SLICE_SIZE {
             UnackedMessage::new_sliced(message)
         } else {
-            UnackedMessage::Small { message, last_sent: None }
+            UnackedMessage::Small {
+                message,
+                last_sent: None,
+                retransmits: 0,
+            }
         };
 
         self.unacked_messages.insert(self.next_reliable_message_id, unacked_message);
+        if let Some(deadline) = self.deadline {
+            self.expirations
+                .push_back((self.next_reliable_message_id, self.current_time + deadline));
+        }
         self.next_reliable_message_id += 1;
 
         Ok(())
     }
 
+    /// Same as [`Self::send_message`], but traces the message through a span with the label until it is acked.
+    #[cfg(feature = "tracing")]
+    pub fn send_message_labeled(&mut self, message: Bytes, label: &'static str) -> Result<(), ChannelError> {
+        let message_id = self.next_reliable_message_id;
+        let len = message.len();
+        self.send_message(message)?;
+
+        let span = tracing::debug_span!("renet_message", label, channel_id = self.channel_id, message_id);
+        span.in_scope(|| tracing::debug!(len, "queued"));
+        self.labels.insert(message_id, span);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_acked(&mut self, message_id: u64) {
+        if let Some(span) = self.labels.remove(&message_id) {
+            span.in_scope(|| tracing::debug!("acked"));
+        }
+    }
+
     pub fn process_message_ack(&mut self, message_id: u64) {
         if self.unacked_messages.contains_key(&message_id) {
             let unacked_message = self.unacked_messages.remove(&message_id).unwrap();
@@ -226,6 +397,8 @@ impl SendChannelReliable {
                 unreachable!("called ack on small message but found sliced");
             };
             self.memory_usage_bytes -= payload.len();
+            #[cfg(feature = "tracing")]
+            self.trace_acked(message_id);
         }
     }
 
@@ -242,7 +415,8 @@ impl SendChannelReliable {
             ..
         } = unacked_message
         else {
-            unreachable!("called ack on sliced message but found small");
+            // The sliced message expired and was replaced by a small one
+            return;
         };
 
         if acked[slice_index] {
@@ -255,7 +429,46 @@ impl SendChannelReliable {
         if *num_acked_slices == *num_slices {
             self.memory_usage_bytes -= message.len();
             self.unacked_messages.remove(&message_id);
+            #[cfg(feature = "tracing")]
+            self.trace_acked(message_id);
+        }
+    }
+
+    // Serializes the messages waiting for acknowledgement,
+    // the acks of sliced messages are not kept so all slices are resent after importing.
+    pub fn export_state(&self, buffer: &mut Vec<u8>) {
+        buffer.put_u64(self.next_reliable_message_id);
+        buffer.put_u64(self.unacked_messages.len() as u64);
+        for (&message_id, unacked_message) in self.unacked_messages.iter() {
+            let (UnackedMessage::Small { message, .. } | UnackedMessage::Sliced { message, .. }) = unacked_message;
+            buffer.put_u64(message_id);
+            state::put_bytes(buffer, message);
+        }
+    }
+
+    pub fn import_state(&mut self, octets: &mut Octets) -> Result<(), ImportStateError> {
+        self.next_reliable_message_id = octets.get_u64()?;
+        for _ in 0..state::get_len(octets)? {
+            let message_id = octets.get_u64()?;
+            let message = state::get_bytes(octets)?;
+            if message_id >= self.next_reliable_message_id {
+                return Err(ImportStateError::InvalidState);
+            }
+
+            self.memory_usage_bytes += message.len();
+            let unacked_message = if message.len() > SLICE_SIZE {
+                UnackedMessage::new_sliced(message)
+            } else {
+                UnackedMessage::Small {
+                    message,
+                    last_sent: None,
+                    retransmits: 0,
+                }
+            };
+            self.unacked_messages.insert(message_id, unacked_message);
         }
+
+        Ok(())
     }
 }
 
@@ -275,15 +488,42 @@ impl ReceiveChannelReliable {
             reliable_order,
             memory_usage_bytes: 0,
             max_memory_usage_bytes,
+            deadline: false,
+            #[cfg(feature = "frame_log")]
+            arrived: Vec::new(),
         }
     }
 
+    /// Discards the messages that expired in the sender, see [`SendChannelReliable::with_deadline`].
+    pub fn with_deadline(mut self) -> Self {
+        self.deadline = true;
+        self
+    }
+
+    pub fn has_deadline(&self) -> bool {
+        self.deadline
+    }
+
+    // Includes the memory reserved for partially received slices
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn slices_memory_usage_bytes(&self) -> usize {
+        self.slices.values().map(|slices| slices.num_slices * SLICE_SIZE).sum()
+    }
+
     pub fn process_message(&mut self, message: Bytes, message_id: u64) -> Result<(), ChannelError> {
         if message_id < self.oldest_pending_message_id {
             // Discard old message already received
             return Ok(());
         }
 
+        if let Some(slice_constructor) = self.slices.remove(&message_id) {
+            // A sliced message can be replaced by a small one when it expires in the sender
+            self.memory_usage_bytes -= slice_constructor.num_slices * SLICE_SIZE;
+        }
+
         match &mut self.reliable_order {
             ReliableOrder::Ordered => {
                 if let btree_map::Entry::Vacant(entry) = self.messages.entry(message_id) {
@@ -292,6 +532,8 @@ impl ReceiveChannelReliable {
                     }
                     self.memory_usage_bytes += message.len();
 
+                    #[cfg(feature = "frame_log")]
+                    self.arrived.push(message.clone());
                     entry.insert(message);
                 }
             }
@@ -310,6 +552,8 @@ impl ReceiveChannelReliable {
                     self.memory_usage_bytes += message.len();
 
                     received_messages.insert(message_id);
+                    #[cfg(feature = "frame_log")]
+                    self.arrived.push(message.clone());
                     self.messages.insert(message_id, message);
                 }
             }
@@ -340,14 +584,45 @@ impl ReceiveChannelReliable {
         if let Some(message) = slice_constructor.process_slice(slice.slice_index, &slice.payload)? {
             // Memory usage is re-added with the exactly message size
             self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
-            self.process_message(message, slice.message_id)?;
             self.slices.remove(&slice.message_id);
+            self.process_message(message, slice.message_id)?;
         }
 
         Ok(())
     }
 
+    // Returns the new messages received since the last call in arrival order, before being reordered.
+    // Messages that expired in the sender are skipped.
+    #[cfg(feature = "frame_log")]
+    pub fn take_arrived(&mut self) -> Vec<Bytes> {
+        let arrived = std::mem::take(&mut self.arrived);
+        if !self.deadline {
+            return arrived;
+        }
+
+        arrived
+            .into_iter()
+            .filter(|message| message.first() == Some(&DEADLINE_MESSAGE))
+            .map(|message| message.slice(1..))
+            .collect()
+    }
+
     pub fn receive_message(&mut self) -> Option<Bytes> {
+        loop {
+            let message = self.receive_next_message()?;
+            if !self.deadline {
+                return Some(message);
+            }
+
+            match message.first() {
+                Some(&DEADLINE_MESSAGE) => return Some(message.slice(1..)),
+                // Expired in the sender
+                _ => continue,
+            }
+        }
+    }
+
+    fn receive_next_message(&mut self) -> Option<Bytes> {
         match &mut self.reliable_order {
             ReliableOrder::Ordered => {
                 let message = self.messages.remove(&self.oldest_pending_message_id)?;
@@ -373,6 +648,74 @@ impl ReceiveChannelReliable {
             }
         }
     }
+
+    pub fn export_state(&self, buffer: &mut Vec<u8>) {
+        buffer.put_u64(self.oldest_pending_message_id);
+        buffer.put_u64(self.messages.len() as u64);
+        for (&message_id, message) in self.messages.iter() {
+            buffer.put_u64(message_id);
+            state::put_bytes(buffer, message);
+        }
+
+        buffer.put_u64(self.slices.len() as u64);
+        for slice_constructor in self.slices.values() {
+            slice_constructor.export_state(buffer);
+        }
+
+        match &self.reliable_order {
+            ReliableOrder::Ordered => buffer.put_u8(0),
+            ReliableOrder::Unordered {
+                most_recent_message_id,
+                received_messages,
+            } => {
+                buffer.put_u8(1);
+                buffer.put_u64(*most_recent_message_id);
+                buffer.put_u64(received_messages.len() as u64);
+                for &message_id in received_messages.iter() {
+                    buffer.put_u64(message_id);
+                }
+            }
+        }
+    }
+
+    pub fn import_state(&mut self, octets: &mut Octets) -> Result<(), ImportStateError> {
+        self.oldest_pending_message_id = octets.get_u64()?;
+        for _ in 0..state::get_len(octets)? {
+            let message_id = octets.get_u64()?;
+            let message = state::get_bytes(octets)?;
+            self.memory_usage_bytes += message.len();
+            self.messages.insert(message_id, message);
+        }
+
+        for _ in 0..state::get_len(octets)? {
+            let slice_constructor = SliceConstructor::import_state(octets)?;
+            self.memory_usage_bytes += slice_constructor.num_slices * SLICE_SIZE;
+            self.slices.insert(slice_constructor.message_id, slice_constructor);
+        }
+
+        match (&mut self.reliable_order, octets.get_u8()?) {
+            (ReliableOrder::Ordered, 0) => {}
+            (
+                ReliableOrder::Unordered {
+                    most_recent_message_id,
+                    received_messages,
+                },
+                1,
+            ) => {
+                *most_recent_message_id = octets.get_u64()?;
+                for _ in 0..state::get_len(octets)? {
+                    received_messages.insert(octets.get_u64()?);
+                }
+            }
+            _ => return Err(ImportStateError::ConfigMismatch),
+        }
+
+        if self.memory_usage_bytes > self.max_memory_usage_bytes {
+            return Err(ImportStateError::ConfigMismatch);
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -389,7 +732,7 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, max_memory, None);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -436,6 +779,75 @@ mod tests {
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn max_in_flight() {
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let resend_time = Duration::from_millis(100);
+        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX, Some(2));
+
+        send.send_message(vec![1].into()).unwrap();
+        send.send_message(vec![2].into()).unwrap();
+        assert!(!send.can_send_message(1));
+        assert_eq!(
+            send.send_message(vec![3].into()),
+            Err(ChannelError::ReliableChannelMaxInFlightReached)
+        );
+
+        send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        send.process_message_ack(0);
+        assert!(send.can_send_message(1));
+        send.send_message(vec![3].into()).unwrap();
+    }
+
+    #[test]
+    fn max_retransmits() {
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let resend_time = Duration::from_millis(100);
+        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX, None).with_max_retransmits(Some(2));
+
+        send.send_message(vec![1, 2, 3].into()).unwrap();
+        send.send_message(vec![0; SLICE_SIZE + 1].into()).unwrap();
+        // The first send is not a retransmit
+        assert_eq!(
+            send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO).len(),
+            3
+        );
+        assert_eq!(send.retransmits(), 0);
+
+        for i in 1..=2 {
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, resend_time * i);
+            assert_eq!(packets.len(), 3);
+            assert!(!send.retransmit_limit_reached());
+        }
+        assert_eq!(send.retransmits(), 6);
+
+        // Acked slices stop counting
+        send.process_slice_message_ack(1, 0);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, resend_time * 3);
+        assert!(packets.is_empty());
+        assert!(send.retransmit_limit_reached());
+        assert_eq!(send.retransmits(), 6);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn labeled_message_acked() {
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let mut send = SendChannelReliable::new(0, Duration::from_millis(100), usize::MAX, None);
+
+        send.send_message_labeled(vec![1, 2, 3].into(), "match-start").unwrap();
+        send.send_message(vec![4, 5, 6].into()).unwrap();
+        assert!(send.labels.contains_key(&0));
+        assert!(!send.labels.contains_key(&1));
+
+        send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        send.process_message_ack(0);
+        assert!(send.labels.is_empty());
+    }
+
     #[test]
     fn small_packet_unordered() {
         let max_memory: usize = 10000;
@@ -444,7 +856,7 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, false);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, max_memory, None);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -514,7 +926,7 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, max_memory, None);
 
         let message = vec![5; SLICE_SIZE * 3];
 
@@ -557,7 +969,7 @@ mod tests {
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(99, true);
-        let mut send = SendChannelReliable::new(0, resend_time, 101);
+        let mut send = SendChannelReliable::new(0, resend_time, 101, None);
 
         let message = vec![5; 100];
 
@@ -593,7 +1005,7 @@ mod tests {
         let mut sequence: u64 = 0;
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX, None);
 
         let message: Bytes = vec![0u8; 100].into();
         send.send_message(message.clone()).unwrap();
@@ -626,7 +1038,7 @@ mod tests {
         let current_time: Duration = Duration::ZERO;
         let mut available_bytes = u64::MAX;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX, None);
 
         // 4 bytes
         let message: Bytes = vec![0, 1, 2, 3].into();
@@ -645,4 +1057,50 @@ mod tests {
             assert!(len < 1300);
         }
     }
+
+    #[test]
+    fn deadline() {
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let resend_time = Duration::from_millis(100);
+        let deadline = Duration::from_millis(500);
+        let mut recv = ReceiveChannelReliable::new(usize::MAX, true).with_deadline();
+        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX, None).with_deadline(deadline);
+
+        let small_message = Bytes::from(vec![1, 2, 3]);
+        let sliced_message = Bytes::from(vec![5; SLICE_SIZE * 2]);
+        send.send_message(small_message.clone()).unwrap();
+        send.send_message(sliced_message.clone()).unwrap();
+
+        // The first packets are lost
+        send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        assert!(send.update(Duration::from_millis(499)).is_empty());
+        send.send_message(Bytes::from("after")).unwrap();
+
+        let expired = send.update(deadline);
+        assert_eq!(expired, vec![(0, small_message), (1, sliced_message)]);
+
+        // Expired messages are replaced, and skipped by the receiver
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, deadline);
+        assert_eq!(packets.len(), 1);
+        for packet in packets {
+            let Packet::SmallReliable { messages, .. } = packet else {
+                unreachable!();
+            };
+            for (message_id, message) in messages {
+                recv.process_message(message, message_id).unwrap();
+            }
+        }
+
+        assert_eq!(recv.receive_message().unwrap(), "after");
+        assert_eq!(recv.receive_message(), None);
+
+        // Late acks of the sliced message are ignored
+        send.process_slice_message_ack(1, 0);
+        send.process_message_ack(0);
+        send.process_message_ack(1);
+        send.process_message_ack(2);
+        assert!(send.unacked_messages.is_empty());
+        assert_eq!(send.memory_usage_bytes, 0);
+    }
 }
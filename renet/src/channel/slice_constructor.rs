@@ -1,10 +1,15 @@
-use bytes::Bytes;
+use bytes::{BufMut, Bytes};
+use octets::Octets;
 
-use crate::{error::ChannelError, packet::SLICE_SIZE};
+use crate::{
+    error::{ChannelError, ImportStateError},
+    packet::SLICE_SIZE,
+    state,
+};
 
 #[derive(Debug, Clone)]
 pub struct SliceConstructor {
-    message_id: u64,
+    pub message_id: u64,
     pub num_slices: usize,
     num_received_slices: usize,
     received: Vec<bool>,
@@ -72,4 +77,32 @@ impl SliceConstructor {
 
         Ok(None)
     }
+
+    pub fn export_state(&self, buffer: &mut Vec<u8>) {
+        buffer.put_u64(self.message_id);
+        let received: Vec<u8> = self.received.iter().map(|&received| received as u8).collect();
+        state::put_bytes(buffer, &received);
+        state::put_bytes(buffer, &self.sliced_data);
+    }
+
+    pub fn import_state(octets: &mut Octets) -> Result<Self, ImportStateError> {
+        let message_id = octets.get_u64()?;
+        let received: Vec<bool> = state::get_bytes(octets)?.iter().map(|&received| received != 0).collect();
+        let sliced_data = state::get_bytes(octets)?.to_vec();
+
+        let num_slices = received.len();
+        let num_received_slices = received.iter().filter(|&&received| received).count();
+        let max_len = num_slices * SLICE_SIZE;
+        if num_slices == 0 || num_received_slices == num_slices || !(max_len - SLICE_SIZE..=max_len).contains(&sliced_data.len()) {
+            return Err(ImportStateError::InvalidState);
+        }
+
+        Ok(Self {
+            message_id,
+            num_slices,
+            num_received_slices,
+            received,
+            sliced_data,
+        })
+    }
 }
@@ -18,6 +18,8 @@ pub struct SendChannelUnreliable {
     sliced_message_id: u64,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    max_queue: Option<usize>,
+    dropped_messages: u64,
 }
 
 #[derive(Debug)]
@@ -28,6 +30,8 @@ pub struct ReceiveChannelUnreliable {
     slices_last_received: BTreeMap<u64, Duration>,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    #[cfg(feature = "frame_log")]
+    arrived: Vec<Bytes>,
 }
 
 impl SendChannelUnreliable {
@@ -38,9 +42,22 @@ impl SendChannelUnreliable {
             sliced_message_id: 0,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            max_queue: None,
+            dropped_messages: 0,
         }
     }
 
+    /// Drops the oldest queued message when sending a message with the queue full.
+    pub fn with_max_queue(mut self, max_queue: Option<usize>) -> Self {
+        self.max_queue = max_queue;
+        self
+    }
+
+    /// Number of messages dropped because the queue was full.
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
@@ -49,6 +66,14 @@ impl SendChannelUnreliable {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
+    pub fn has_pending_data(&self) -> bool {
+        !self.unreliable_messages.is_empty()
+    }
+
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
     pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
         let mut packets: Vec<Packet> = vec![];
         let mut small_messages: Vec<Bytes> = vec![];
@@ -132,6 +157,18 @@ impl SendChannelUnreliable {
                 Consider breaking your message into smaller ones or using a reliable channel");
         }
 
+        if let Some(max_queue) = self.max_queue {
+            while self.unreliable_messages.len() >= max_queue {
+                let Some(oldest) = self.unreliable_messages.pop_front() else {
+                    // A max queue of zero drops every message
+                    self.dropped_messages += 1;
+                    return;
+                };
+                self.memory_usage_bytes -= oldest.len();
+                self.dropped_messages += 1;
+            }
+        }
+
         self.memory_usage_bytes += message.len();
         self.unreliable_messages.push_back(message);
     }
@@ -146,9 +183,20 @@ impl ReceiveChannelUnreliable {
             messages: VecDeque::new(),
             memory_usage_bytes: 0,
             max_memory_usage_bytes,
+            #[cfg(feature = "frame_log")]
+            arrived: Vec::new(),
         }
     }
 
+    // Includes the memory reserved for partially received slices
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn slices_memory_usage_bytes(&self) -> usize {
+        self.slices.values().map(|slices| slices.num_slices * SLICE_SIZE).sum()
+    }
+
     pub fn process_message(&mut self, message: Bytes) {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
             log::warn!(
@@ -159,6 +207,8 @@ impl ReceiveChannelUnreliable {
         }
 
         self.memory_usage_bytes += message.len();
+        #[cfg(feature = "frame_log")]
+        self.arrived.push(message.clone());
         self.messages.push_back(message);
     }
 
@@ -186,6 +236,8 @@ impl ReceiveChannelUnreliable {
             self.slices_last_received.remove(&slice.message_id);
             self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
             self.memory_usage_bytes += message.len();
+            #[cfg(feature = "frame_log")]
+            self.arrived.push(message.clone());
             self.messages.push_back(message);
         } else {
             self.slices_last_received.insert(slice.message_id, current_time);
@@ -222,6 +274,12 @@ impl ReceiveChannelUnreliable {
 
         None
     }
+
+    // Returns the complete messages received since the last call, in arrival order
+    #[cfg(feature = "frame_log")]
+    pub fn take_arrived(&mut self) -> Vec<Bytes> {
+        std::mem::take(&mut self.arrived)
+    }
 }
 
 #[cfg(test)]
@@ -324,6 +382,28 @@ mod tests {
         assert!(recv.receive_message().is_none());
     }
 
+    #[test]
+    fn max_queue() {
+        let mut sequence: u64 = 0;
+        let mut available_bytes = u64::MAX;
+        let mut send = SendChannelUnreliable::new(0, usize::MAX).with_max_queue(Some(4));
+
+        for i in 0..100u8 {
+            send.send_message(vec![i].into());
+        }
+        assert_eq!(send.unreliable_messages.len(), 4);
+        assert_eq!(send.memory_usage_bytes, 4);
+        assert_eq!(send.dropped_messages(), 96);
+
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let Packet::SmallUnreliable { messages, .. } = &packets[0] else {
+            unreachable!();
+        };
+        // Only the newest messages are kept
+        let expected: Vec<Bytes> = (96..100u8).map(|i| vec![i].into()).collect();
+        assert_eq!(messages, &expected);
+    }
+
     #[test]
     fn available_bytes() {
         let mut sequence: u64 = 0;
@@ -4,6 +4,45 @@ const RESOLUTION: Duration = Duration::from_millis(300);
 const WINDOW: Duration = Duration::from_millis(6000);
 const SIZE: usize = (WINDOW.as_millis() / RESOLUTION.as_millis()) as usize;
 
+// Ack latency buckets grow by 25%, starting at 1ms, the last one reaches above 7 seconds
+const LATENCY_BUCKETS: usize = 40;
+const LATENCY_BUCKET_GROWTH: f64 = 1.25;
+
+/// Distribution of the time from sending a packet of a reliable channel to receiving its ack,
+/// see [`RenetClient::ack_latency_stats`][crate::RenetClient::ack_latency_stats].
+///
+/// Resent messages are measured from their last send. A resend time below the p95 resends
+/// most messages that were only waiting for their ack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AckLatencyStats {
+    /// Number of acks measured, the other values are zero when there are none.
+    pub samples: u64,
+    pub min: Duration,
+    pub avg: Duration,
+    /// Approximated from the histogram, within 25% of the real value.
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+/// Bytes of the packets sent and received on a channel since the connection started,
+/// see [`RenetClient::channel_bytes`][crate::RenetClient::channel_bytes].
+///
+/// Counts the serialized packets including their headers, acks and other control packets are not counted in any channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelBytes {
+    pub sent: u64,
+    pub received: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AckLatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+    samples: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
 #[derive(Debug, Default)]
 pub struct ConnectionStats {
     packets_sent: [u64; SIZE],
@@ -111,6 +150,68 @@ impl ConnectionStats {
     }
 }
 
+impl Default for AckLatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; LATENCY_BUCKETS],
+            samples: 0,
+            total: Duration::ZERO,
+            min: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl AckLatencyHistogram {
+    fn bucket(latency: Duration) -> usize {
+        let millis = latency.as_secs_f64() * 1000.;
+        if millis <= 1. {
+            return 0;
+        }
+
+        (millis.log(LATENCY_BUCKET_GROWTH).ceil() as usize).min(LATENCY_BUCKETS - 1)
+    }
+
+    fn bucket_upper_bound(bucket: usize) -> Duration {
+        Duration::from_secs_f64(LATENCY_BUCKET_GROWTH.powi(bucket as i32) / 1000.)
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples == 0 || latency < self.min {
+            self.min = latency;
+        }
+        self.max = self.max.max(latency);
+        self.buckets[Self::bucket(latency)] += 1;
+        self.samples += 1;
+        self.total += latency;
+    }
+
+    pub fn stats(&self) -> AckLatencyStats {
+        if self.samples == 0 {
+            return AckLatencyStats::default();
+        }
+
+        let p95_rank = self.samples.saturating_mul(95).div_ceil(100);
+        let mut count = 0;
+        let mut p95 = self.max;
+        for (bucket, &samples) in self.buckets.iter().enumerate() {
+            count += samples;
+            if count >= p95_rank {
+                p95 = Self::bucket_upper_bound(bucket).clamp(self.min, self.max);
+                break;
+            }
+        }
+
+        AckLatencyStats {
+            samples: self.samples,
+            min: self.min,
+            avg: self.total / self.samples as u32,
+            p95,
+            max: self.max,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,6 +242,27 @@ mod tests {
         assert_eq!(window.bytes_sent_per_second(current_time), 1000.);
     }
 
+    #[test]
+    fn ack_latency() {
+        let mut histogram = AckLatencyHistogram::default();
+        assert_eq!(histogram.stats(), AckLatencyStats::default());
+
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+
+        let stats = histogram.stats();
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.avg, Duration::from_micros(50_500));
+        assert!(stats.p95 >= Duration::from_millis(95) && stats.p95 <= Duration::from_millis(100));
+
+        // Latencies above the last bucket are kept in it
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.stats().max, Duration::from_secs(60));
+    }
+
     #[test]
     fn packet_loss() {
         let mut current_time = Duration::ZERO;
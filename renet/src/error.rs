@@ -21,6 +21,10 @@ pub enum DisconnectReason {
     SendChannelError { channel_id: u8, error: ChannelError },
     /// Error occurred in a receive channel
     ReceiveChannelError { channel_id: u8, error: ChannelError },
+    /// The channels of the other side of the connection don't match, see [`ConnectionConfig::validate_config`][crate::ConnectionConfig::validate_config]
+    ConfigMismatch,
+    /// A reliable message was not acknowledged after the maximum of retransmits, see [`ConnectionConfig::max_retransmits`][crate::ConnectionConfig::max_retransmits]
+    ReliableTimeout { channel_id: u8 },
 }
 
 /// Possibles errors that can occur in a channel.
@@ -30,6 +34,10 @@ pub enum ChannelError {
     ReliableChannelMaxMemoryReached,
     /// Received an invalid slice message in the channel.
     InvalidSliceMessage,
+    /// Reliable channel reached maximum allowed messages waiting for acknowledgement
+    ReliableChannelMaxInFlightReached,
+    /// Received a message without a valid global ordering sequence.
+    InvalidGlobalSequence,
 }
 
 impl fmt::Display for ChannelError {
@@ -39,6 +47,8 @@ impl fmt::Display for ChannelError {
         match *self {
             ReliableChannelMaxMemoryReached => write!(fmt, "reliable channel memory usage was exausted"),
             InvalidSliceMessage => write!(fmt, "received an invalid slice packet"),
+            ReliableChannelMaxInFlightReached => write!(fmt, "reliable channel reached the maximum of messages in flight"),
+            InvalidGlobalSequence => write!(fmt, "received a message with an invalid global sequence"),
         }
     }
 }
@@ -56,12 +66,54 @@ impl fmt::Display for DisconnectReason {
             ReceivedInvalidChannelId(id) => write!(fmt, "received message with invalid channel {id}"),
             SendChannelError { channel_id, error } => write!(fmt, "send channel {channel_id} with error: {error}"),
             ReceiveChannelError { channel_id, error } => write!(fmt, "receive channel {channel_id} with error: {error}"),
+            ConfigMismatch => write!(fmt, "connection config does not match the other side"),
+            ReliableTimeout { channel_id } => write!(
+                fmt,
+                "reliable message in channel {channel_id} was not acknowledged after the maximum of retransmits"
+            ),
         }
     }
 }
 
 impl std::error::Error for ChannelError {}
 
+/// Reason for renet to drop a received packet, see [`RenetServer::process_packet_from`][crate::RenetServer::process_packet_from].
+///
+/// Duplicate and out of window packets are expected on unreliable networks, the other errors disconnect the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessError {
+    /// No connection with the client id
+    ClientNotFound,
+    /// The packet could not be deserialized
+    Malformed(SerializationError),
+    /// The packet was already received
+    Duplicate,
+    /// The packet with unreliable messages is older than the received packets still tracked, so it may have been received already,
+    /// see [`NetworkInfo::out_of_window_packets`][crate::NetworkInfo::out_of_window_packets]
+    OutOfWindow,
+    /// The packet has a message for a channel that does not exist
+    UnknownChannel(u8),
+    /// The receive channel failed to process the messages of the packet
+    ReceiveChannel { channel_id: u8, error: ChannelError },
+}
+
+impl std::error::Error for ProcessError {}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ProcessError::*;
+
+        match *self {
+            ClientNotFound => write!(fmt, "client with given id was not found"),
+            Malformed(err) => write!(fmt, "failed to deserialize packet: {err}"),
+            Duplicate => write!(fmt, "packet was already received"),
+            OutOfWindow => write!(fmt, "packet is older than the tracked received packets"),
+            UnknownChannel(id) => write!(fmt, "received message with invalid channel {id}"),
+            ReceiveChannel { channel_id, error } => write!(fmt, "receive channel {channel_id} with error: {error}"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ClientNotFound;
 
@@ -72,3 +124,70 @@ impl fmt::Display for ClientNotFound {
         write!(fmt, "client with given id was not found")
     }
 }
+
+/// The channel reached the maximum number of reliable messages in flight, see [`ChannelConfig::max_in_flight`][crate::ChannelConfig::max_in_flight].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelFull {
+    pub channel_id: u8,
+}
+
+impl std::error::Error for ChannelFull {}
+
+impl fmt::Display for ChannelFull {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "channel {} reached the maximum of messages in flight", self.channel_id)
+    }
+}
+
+/// The packets of renet are bigger than the transport can send without fragmenting or rejecting them,
+/// see [`ConnectionConfig::validate_packet_size`][crate::ConnectionConfig::validate_packet_size].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSizeError {
+    /// Largest packet generated by renet.
+    pub max_packet_size: usize,
+    /// Largest packet supported by the transport.
+    pub transport_max_packet_size: usize,
+}
+
+impl std::error::Error for PacketSizeError {}
+
+impl fmt::Display for PacketSizeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "renet packets can have up to {} bytes but the transport only supports {} bytes",
+            self.max_packet_size, self.transport_max_packet_size
+        )
+    }
+}
+
+/// Possible errors when importing a connection state, see [`RenetServer::import_state`][crate::RenetServer::import_state].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportStateError {
+    /// The state was exported with an incompatible format version
+    UnsupportedVersion { version: u8 },
+    /// The state is truncated or corrupted
+    InvalidState,
+    /// The state has channels that don't match the current connection config
+    ConfigMismatch,
+}
+
+impl std::error::Error for ImportStateError {}
+
+impl fmt::Display for ImportStateError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ImportStateError::*;
+
+        match *self {
+            UnsupportedVersion { version } => write!(fmt, "unsupported state format version {version}"),
+            InvalidState => write!(fmt, "invalid connection state"),
+            ConfigMismatch => write!(fmt, "connection state does not match the connection config"),
+        }
+    }
+}
+
+impl From<octets::BufferTooShortError> for ImportStateError {
+    fn from(_: octets::BufferTooShortError) -> Self {
+        ImportStateError::InvalidState
+    }
+}
@@ -4,10 +4,15 @@ mod error;
 mod packet;
 mod remote_connection;
 mod server;
+mod state;
+mod time_sync;
 
-pub use channel::{ChannelConfig, DefaultChannel, SendType};
-pub use error::{ChannelError, ClientNotFound, DisconnectReason};
-pub use remote_connection::{ConnectionConfig, NetworkInfo, RenetClient, RenetConnectionStatus};
+pub use channel::{ChannelConfig, DefaultChannel, ExpiredMessage, SendType};
+pub use connection_stats::{AckLatencyStats, ChannelBytes};
+pub use error::{ChannelError, ChannelFull, ClientNotFound, DisconnectReason, ImportStateError, PacketSizeError, ProcessError};
+#[cfg(feature = "frame_log")]
+pub use remote_connection::FrameLogEntry;
+pub use remote_connection::{ConnectionConfig, MemoryStats, NetworkInfo, RenetClient, RenetConnectionStatus};
 pub use server::{RenetServer, ServerEvent};
 
 pub use bytes::Bytes;
@@ -5,6 +5,9 @@ pub type Payload = Vec<u8>;
 
 // Sliced messages are split into SLICE_SIZE bytes chunks
 pub const SLICE_SIZE: usize = 1200;
+// Largest packet with messages generated by a connection: a slice with its header (type, varint sequence, channel,
+// 4 varints for message id, slice index, number of slices and payload length)
+pub const MAX_PACKET_SIZE: usize = SLICE_SIZE + 40;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Slice {
@@ -46,6 +49,23 @@ pub enum Packet {
         sequence: u64,
         ack_ranges: Vec<Range<u64>>,
     },
+    // Sent by the client to estimate the server clock, times are in microseconds
+    TimeRequest {
+        sequence: u64,
+        client_time: u64,
+    },
+    // Hash of the ConnectionConfig, sent until acked when config validation is enabled
+    ConfigHash {
+        sequence: u64,
+        hash: u64,
+    },
+    // Sent by the server in response to a TimeRequest, with its clock when receiving the request and when sending the response
+    TimeResponse {
+        sequence: u64,
+        client_time: u64,
+        server_receive_time: u64,
+        server_send_time: u64,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -88,7 +108,21 @@ impl Packet {
             | Packet::SmallUnreliable { sequence, .. }
             | Packet::UnreliableSlice { sequence, .. }
             | Packet::ReliableSlice { sequence, .. }
-            | Packet::Ack { sequence, .. } => *sequence,
+            | Packet::Ack { sequence, .. }
+            | Packet::ConfigHash { sequence, .. }
+            | Packet::TimeRequest { sequence, .. }
+            | Packet::TimeResponse { sequence, .. } => *sequence,
+        }
+    }
+
+    /// Returns the channel of the messages in the packet, or None for control packets.
+    pub fn channel_id(&self) -> Option<u8> {
+        match self {
+            Packet::SmallReliable { channel_id, .. }
+            | Packet::SmallUnreliable { channel_id, .. }
+            | Packet::UnreliableSlice { channel_id, .. }
+            | Packet::ReliableSlice { channel_id, .. } => Some(*channel_id),
+            Packet::Ack { .. } | Packet::ConfigHash { .. } | Packet::TimeRequest { .. } | Packet::TimeResponse { .. } => None,
         }
     }
 
@@ -200,6 +234,28 @@ impl Packet {
                     previous_range_start = range.start;
                 }
             }
+            Packet::ConfigHash { sequence, hash } => {
+                b.put_u8(7)?;
+                b.put_varint(*sequence)?;
+                b.put_u64(*hash)?;
+            }
+            Packet::TimeRequest { sequence, client_time } => {
+                b.put_u8(5)?;
+                b.put_varint(*sequence)?;
+                b.put_varint(*client_time)?;
+            }
+            Packet::TimeResponse {
+                sequence,
+                client_time,
+                server_receive_time,
+                server_send_time,
+            } => {
+                b.put_u8(6)?;
+                b.put_varint(*sequence)?;
+                b.put_varint(*client_time)?;
+                b.put_varint(*server_receive_time)?;
+                b.put_varint(*server_send_time)?;
+            }
         }
 
         Ok(before - b.cap())
@@ -346,6 +402,34 @@ impl Packet {
 
                 Ok(Packet::Ack { sequence, ack_ranges })
             }
+            5 => {
+                // TimeRequest
+                let sequence = b.get_varint()?;
+                let client_time = b.get_varint()?;
+
+                Ok(Packet::TimeRequest { sequence, client_time })
+            }
+            6 => {
+                // TimeResponse
+                let sequence = b.get_varint()?;
+                let client_time = b.get_varint()?;
+                let server_receive_time = b.get_varint()?;
+                let server_send_time = b.get_varint()?;
+
+                Ok(Packet::TimeResponse {
+                    sequence,
+                    client_time,
+                    server_receive_time,
+                    server_send_time,
+                })
+            }
+            7 => {
+                // ConfigHash
+                let sequence = b.get_varint()?;
+                let hash = b.get_u64()?;
+
+                Ok(Packet::ConfigHash { sequence, hash })
+            }
             _ => Err(SerializationError::InvalidPacketType),
         }
     }
@@ -451,4 +535,69 @@ mod tests {
         let recv_packet = Packet::from_bytes(&mut b).unwrap();
         assert_eq!(packet, recv_packet);
     }
+
+    #[test]
+    fn max_packet_size() {
+        let mut buffer = [0u8; 1400];
+        // Largest values that fit in a varint
+        let max_varint = (1 << 62) - 1;
+        let packet = Packet::ReliableSlice {
+            sequence: max_varint,
+            channel_id: 0,
+            slice: Slice {
+                message_id: max_varint,
+                slice_index: max_varint as usize,
+                num_slices: max_varint as usize,
+                payload: vec![0; SLICE_SIZE].into(),
+            },
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+        assert!(len <= MAX_PACKET_SIZE);
+    }
+
+    #[test]
+    fn serialize_config_hash_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::ConfigHash {
+            sequence: 0,
+            hash: u64::MAX,
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_time_packets() {
+        let mut buffer = [0u8; 1300];
+
+        let packets = [
+            Packet::TimeRequest {
+                sequence: 0,
+                client_time: 1_500_000,
+            },
+            Packet::TimeResponse {
+                sequence: 1,
+                client_time: 1_500_000,
+                server_receive_time: 3_000_000,
+                server_send_time: 3_016_000,
+            },
+        ];
+
+        for packet in packets {
+            let mut b = octets::OctetsMut::with_slice(&mut buffer);
+            packet.to_bytes(&mut b).unwrap();
+
+            let mut b = octets::Octets::with_slice(&buffer);
+            let recv_packet = Packet::from_bytes(&mut b).unwrap();
+            assert_eq!(packet, recv_packet);
+        }
+    }
 }
@@ -1,17 +1,25 @@
+use crate::channel::global_order::GlobalOrder;
 use crate::channel::reliable::{ReceiveChannelReliable, SendChannelReliable};
 use crate::channel::unreliable::{ReceiveChannelUnreliable, SendChannelUnreliable};
-use crate::channel::{ChannelConfig, DefaultChannel, SendType};
-use crate::connection_stats::ConnectionStats;
-use crate::error::DisconnectReason;
-use crate::packet::{Packet, Payload};
-use bytes::Bytes;
+use crate::channel::{ChannelConfig, DefaultChannel, ExpiredMessage, SendType};
+use crate::connection_stats::{AckLatencyHistogram, AckLatencyStats, ChannelBytes, ConnectionStats};
+use crate::error::{ChannelError, ChannelFull, DisconnectReason, ImportStateError, PacketSizeError, ProcessError};
+use crate::packet::{Packet, Payload, MAX_PACKET_SIZE};
+use crate::state;
+use crate::time_sync::TimeSync;
+use bytes::{BufMut, Bytes};
 use octets::OctetsMut;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ops::Range;
 use std::time::Duration;
 
 /// Configuration for a renet connection and its channels.
+///
+/// Connections have no randomized behavior: packet sequences and message ids start at 0
+/// and time only advances with the durations passed to `update`, so the same inputs always produce the same packets.
+/// Randomness only exists in the transports, like the encryption keys and nonces of `renet_netcode`
+/// (which must stay unpredictable) and the seeded network conditions of the `renet_steam` mock.
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
     /// The number of bytes that is available per update tick to send messages.
@@ -27,6 +35,113 @@ pub struct ConnectionConfig {
     /// Each tick, the first channel can consume up to `available_bytes_per_tick`,
     /// used bytes are removed from it and passed to the next channel
     pub client_channels_config: Vec<ChannelConfig>,
+    /// When enabled, messages from all reliable channels are received in the same order they were sent,
+    /// relative to each other, not only relative to messages of the same channel.
+    /// Each reliable message is prefixed with a sequence shared by all channels.
+    ///
+    /// This adds cross-channel head-of-line blocking: a lost packet in one channel
+    /// delays the messages of every other reliable channel until it's resent, which can add
+    /// one or more resend times of latency under packet loss. Unreliable channels are not affected.
+    /// Channels with [`SendType::ReliableWithDeadline`] are not part of the global order,
+    /// since their expired messages would block the other channels.
+    /// Must be the same for the server and the client.
+    /// Default: false
+    pub global_ordering: bool,
+    /// When set, the client sends a time sync request every interval to estimate the server clock,
+    /// see [`RenetClient::estimated_server_time_offset_secs`]. The server always answers the requests.
+    /// Default: None
+    pub time_sync_interval: Option<Duration>,
+    /// When enabled, both sides send the [`Self::config_hash`] once connected and disconnect with
+    /// [`DisconnectReason::ConfigMismatch`] if the hash of the other side is different.
+    /// Both the server and the client must enable it, a side with it disabled ignores the hash of the other one.
+    /// Leave it disabled for intentional asymmetric setups.
+    /// Default: false
+    pub validate_config: bool,
+    /// Maximum number of times a reliable message or slice is resent without being acknowledged,
+    /// when reached the connection is disconnected with [`DisconnectReason::ReliableTimeout`]
+    /// instead of resending forever to a peer that stopped acknowledging but did not time out in the transport.
+    /// Resent messages are counted by [`NetworkInfo::retransmits`].
+    /// Default: None
+    pub max_retransmits: Option<u32>,
+}
+
+impl ConnectionConfig {
+    /// Returns the size in bytes of the largest packet generated by connections,
+    /// messages bigger than the fragment size are sliced to fit in it.
+    pub fn max_packet_size(&self) -> usize {
+        MAX_PACKET_SIZE
+    }
+
+    /// Checks that packets fit in the largest packet supported by the transport,
+    /// bigger packets would be rejected or fragmented by the transport. Meant to be checked at startup.
+    pub fn validate_packet_size(&self, transport_max_packet_size: usize) -> Result<(), PacketSizeError> {
+        if self.max_packet_size() > transport_max_packet_size {
+            return Err(PacketSizeError {
+                max_packet_size: self.max_packet_size(),
+                transport_max_packet_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the time after which a reliable message that is never acknowledged disconnects the connection
+    /// with [`DisconnectReason::ReliableTimeout`], for the reliable channel with the shortest resend time.
+    /// None without [`Self::max_retransmits`] or reliable channels. Transports should time out silent connections before it.
+    ///
+    /// Channels with a zero resend time are ignored, they resend on every update so their timeout depends on the update rate.
+    pub fn reliable_timeout(&self) -> Option<Duration> {
+        let max_retransmits = self.max_retransmits?;
+        self.server_channels_config
+            .iter()
+            .chain(self.client_channels_config.iter())
+            .filter_map(|config| match config.send_type {
+                SendType::Unreliable => None,
+                SendType::ReliableOrdered { resend_time }
+                | SendType::ReliableUnordered { resend_time }
+                | SendType::ReliableWithDeadline { resend_time, .. } => Some(resend_time),
+            })
+            .filter(|resend_time| !resend_time.is_zero())
+            .min()
+            .map(|resend_time| resend_time * max_retransmits.saturating_add(1))
+    }
+
+    /// Returns a hash of the channel ids and send types of the server and client channels and of [`Self::global_ordering`],
+    /// stable between builds and platforms. Memory limits and the channels priority order are not included.
+    pub fn config_hash(&self) -> u64 {
+        // FNV-1a, the std hasher is not guaranteed to be the same across builds
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut write = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        };
+
+        // Reliable messages are prefixed with the global sequence
+        write(self.global_ordering as u8);
+        for channels_config in [&self.server_channels_config, &self.client_channels_config] {
+            let mut channels: Vec<(u8, u8)> = channels_config
+                .iter()
+                .map(|config| {
+                    let send_type = match config.send_type {
+                        SendType::Unreliable => 0,
+                        SendType::ReliableOrdered { .. } => 1,
+                        SendType::ReliableUnordered { .. } => 2,
+                        SendType::ReliableWithDeadline { .. } => 3,
+                    };
+                    (config.channel_id, send_type)
+                })
+                .collect();
+            channels.sort_unstable();
+
+            write(channels.len() as u8);
+            for (channel_id, send_type) in channels {
+                write(channel_id);
+                write(send_type);
+            }
+        }
+
+        hash
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,6 +168,7 @@ enum PacketSentInfo {
     Ack {
         largest_acked_packet: u64,
     },
+    ConfigHash,
 }
 
 #[derive(Debug)]
@@ -68,6 +184,60 @@ pub struct NetworkInfo {
     pub packet_loss: f64,
     pub bytes_sent_per_second: f64,
     pub bytes_received_per_second: f64,
+    /// Total number of times reliable messages and slices were resent.
+    pub retransmits: u64,
+    /// Total number of received packets older than the tracked received packets, that can't be told apart from duplicates.
+    /// Their reliable messages are still processed, packets with unreliable messages are dropped with [`ProcessError::OutOfWindow`].
+    pub out_of_window_packets: u64,
+}
+
+/// Message received from the other side of the connection, see [`RenetClient::take_frame_log`].
+#[cfg(feature = "frame_log")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameLogEntry {
+    pub channel_id: u8,
+    /// Arrival order of the message since the last update, starting at 0.
+    pub order: usize,
+    /// Time of the connection when the message was received,
+    /// it only advances when `update` is called so messages of the same frame have the same time.
+    pub received_at: Duration,
+    pub message: Bytes,
+}
+
+/// Memory used by the message buffers of connections, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Messages queued or waiting for acknowledgement in reliable channels.
+    pub reliable_send_bytes: usize,
+    /// Messages received in reliable channels that were not read yet, including the ones waiting for global ordering.
+    pub reliable_receive_bytes: usize,
+    /// Messages queued in unreliable channels.
+    pub unreliable_send_bytes: usize,
+    /// Messages received in unreliable channels that were not read yet.
+    pub unreliable_receive_bytes: usize,
+    /// Memory reserved for the reassembly of partially received sliced messages.
+    pub slice_reassembly_bytes: usize,
+}
+
+impl MemoryStats {
+    /// Returns the sum of all buffers.
+    pub fn total_bytes(&self) -> usize {
+        self.reliable_send_bytes
+            + self.reliable_receive_bytes
+            + self.unreliable_send_bytes
+            + self.unreliable_receive_bytes
+            + self.slice_reassembly_bytes
+    }
+}
+
+impl std::ops::AddAssign for MemoryStats {
+    fn add_assign(&mut self, other: Self) {
+        self.reliable_send_bytes += other.reliable_send_bytes;
+        self.reliable_receive_bytes += other.reliable_receive_bytes;
+        self.unreliable_send_bytes += other.unreliable_send_bytes;
+        self.unreliable_receive_bytes += other.unreliable_receive_bytes;
+        self.slice_reassembly_bytes += other.slice_reassembly_bytes;
+    }
 }
 
 /// The connection status of a [`RenetClient`].
@@ -85,12 +255,26 @@ pub struct RenetClient {
     current_time: Duration,
     sent_packets: BTreeMap<u64, PacketSent>,
     pending_acks: Vec<Range<u64>>,
+    // End of the newest ack range evicted by the limit of pending ranges
+    evicted_acks_end: u64,
+    out_of_window_packets: u64,
     channel_send_order: Vec<ChannelOrder>,
+    receive_channel_order: Vec<u8>,
     send_unreliable_channels: HashMap<u8, SendChannelUnreliable>,
     receive_unreliable_channels: HashMap<u8, ReceiveChannelUnreliable>,
     send_reliable_channels: HashMap<u8, SendChannelReliable>,
     receive_reliable_channels: HashMap<u8, ReceiveChannelReliable>,
+    global_order: Option<GlobalOrder>,
+    time_sync: TimeSync,
+    expired_messages: VecDeque<ExpiredMessage>,
+    // Sent until acked when the config validation is enabled
+    config_hash: Option<u64>,
+    config_hash_acked: bool,
+    #[cfg(feature = "frame_log")]
+    frame_log: Vec<FrameLogEntry>,
     stats: ConnectionStats,
+    ack_latency: HashMap<u8, AckLatencyHistogram>,
+    channel_bytes: HashMap<u8, ChannelBytes>,
     available_bytes_per_tick: u64,
     connection_status: RenetConnectionStatus,
     rtt: f64,
@@ -103,26 +287,40 @@ impl Default for ConnectionConfig {
             available_bytes_per_tick: 60_000,
             server_channels_config: DefaultChannel::config(),
             client_channels_config: DefaultChannel::config(),
+            global_ordering: false,
+            time_sync_interval: None,
+            validate_config: false,
+            max_retransmits: None,
         }
     }
 }
 
 impl RenetClient {
     pub fn new(config: ConnectionConfig) -> Self {
+        let config_hash = config.validate_config.then(|| config.config_hash());
         Self::from_channels(
             config.available_bytes_per_tick,
             config.client_channels_config,
             config.server_channels_config,
+            config.global_ordering,
+            config.time_sync_interval,
+            config_hash,
+            config.max_retransmits,
         )
     }
 
     // When creating a client from the server, the server_channels_config are used as send channels,
     // and the client_channels_config is used as recv channels.
     pub(crate) fn new_from_server(config: ConnectionConfig) -> Self {
+        let config_hash = config.validate_config.then(|| config.config_hash());
         Self::from_channels(
             config.available_bytes_per_tick,
             config.server_channels_config,
             config.client_channels_config,
+            config.global_ordering,
+            config.time_sync_interval,
+            config_hash,
+            config.max_retransmits,
         )
     }
 
@@ -130,6 +328,10 @@ impl RenetClient {
         available_bytes_per_tick: u64,
         send_channels_config: Vec<ChannelConfig>,
         receive_channels_config: Vec<ChannelConfig>,
+        global_ordering: bool,
+        time_sync_interval: Option<Duration>,
+        config_hash: Option<u64>,
+        max_retransmits: Option<u32>,
     ) -> Self {
         let mut send_unreliable_channels = HashMap::new();
         let mut send_reliable_channels = HashMap::new();
@@ -137,14 +339,26 @@ impl RenetClient {
         for channel_config in send_channels_config.iter() {
             match channel_config.send_type {
                 SendType::Unreliable => {
-                    let channel = SendChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
+                    let channel = SendChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes)
+                        .with_max_queue(channel_config.max_unreliable_queue);
                     let old = send_unreliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
                     channel_send_order.push(ChannelOrder::Unreliable(channel_config.channel_id));
                 }
-                SendType::ReliableOrdered { resend_time } | SendType::ReliableUnordered { resend_time } => {
-                    let channel = SendChannelReliable::new(channel_config.channel_id, resend_time, channel_config.max_memory_usage_bytes);
+                SendType::ReliableOrdered { resend_time }
+                | SendType::ReliableUnordered { resend_time }
+                | SendType::ReliableWithDeadline { resend_time, .. } => {
+                    let mut channel = SendChannelReliable::new(
+                        channel_config.channel_id,
+                        resend_time,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.max_in_flight,
+                    )
+                    .with_max_retransmits(max_retransmits);
+                    if let SendType::ReliableWithDeadline { deadline, .. } = channel_config.send_type {
+                        channel = channel.with_deadline(deadline);
+                    }
                     let old = send_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
@@ -155,7 +369,9 @@ impl RenetClient {
 
         let mut receive_unreliable_channels = HashMap::new();
         let mut receive_reliable_channels = HashMap::new();
+        let mut receive_channel_order: Vec<u8> = Vec::with_capacity(receive_channels_config.len());
         for channel_config in receive_channels_config.iter() {
+            receive_channel_order.push(channel_config.channel_id);
             match channel_config.send_type {
                 SendType::Unreliable => {
                     let channel = ReceiveChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
@@ -172,20 +388,46 @@ impl RenetClient {
                     let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
                 }
+                SendType::ReliableWithDeadline { .. } => {
+                    let channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, true).with_deadline();
+                    let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
+                }
             }
         }
 
+        let global_order = global_ordering.then(|| {
+            let max_memory_usage_bytes = receive_channels_config
+                .iter()
+                .filter(|config| !matches!(config.send_type, SendType::Unreliable))
+                .map(|config| config.max_memory_usage_bytes)
+                .sum();
+            GlobalOrder::new(max_memory_usage_bytes)
+        });
+
         Self {
             packet_sequence: 0,
             current_time: Duration::ZERO,
             sent_packets: BTreeMap::new(),
             pending_acks: Vec::new(),
+            evicted_acks_end: 0,
+            out_of_window_packets: 0,
             channel_send_order,
+            receive_channel_order,
             send_unreliable_channels,
             receive_unreliable_channels,
             send_reliable_channels,
             receive_reliable_channels,
+            global_order,
+            time_sync: TimeSync::new(time_sync_interval),
+            expired_messages: VecDeque::new(),
+            config_hash,
+            config_hash_acked: false,
+            #[cfg(feature = "frame_log")]
+            frame_log: Vec::new(),
             stats: ConnectionStats::new(),
+            ack_latency: HashMap::new(),
+            channel_bytes: HashMap::new(),
             rtt: 0.0,
             available_bytes_per_tick,
             connection_status: RenetConnectionStatus::Connecting,
@@ -197,6 +439,29 @@ impl RenetClient {
         self.rtt
     }
 
+    /// Returns the estimated offset in seconds of the server clock to the client clock,
+    /// negative if the server clock is behind. None if no time sync response was received yet,
+    /// see [`ConnectionConfig::time_sync_interval`].
+    ///
+    /// The client clock is the time advanced by [`Self::update`], the server clock is [`RenetServer::current_time`][crate::RenetServer::current_time].
+    /// The estimate uses the sample with the smallest round-trip out of the last 8 responses.
+    /// It's accurate within half of that round-trip if the latency was asymmetric,
+    /// plus the update interval of both sides, since both clocks only advance when updated.
+    /// Jitter only makes some samples worse, the best one is kept while it's in the window.
+    pub fn estimated_server_time_offset_secs(&self) -> Option<f64> {
+        self.time_sync.offset()
+    }
+
+    /// Returns the estimated server clock, see [`Self::estimated_server_time_offset_secs`].
+    pub fn estimated_server_time(&self) -> Option<Duration> {
+        let offset = self.time_sync.offset()?;
+        Some(Duration::from_secs_f64((self.current_time.as_secs_f64() + offset).max(0.0)))
+    }
+
+    pub(crate) fn set_server_time_base(&mut self, server_time_base: Duration) {
+        self.time_sync.set_server_time_base(server_time_base);
+    }
+
     /// Returns the packet loss for the connection.
     pub fn packet_loss(&self) -> f64 {
         self.stats.packet_loss()
@@ -219,9 +484,32 @@ impl RenetClient {
             packet_loss: self.stats.packet_loss(),
             bytes_sent_per_second: self.stats.bytes_sent_per_second(self.current_time),
             bytes_received_per_second: self.stats.bytes_received_per_second(self.current_time),
+            retransmits: self.send_reliable_channels.values().map(|channel| channel.retransmits()).sum(),
+            out_of_window_packets: self.out_of_window_packets,
         }
     }
 
+    /// Returns the time from sending to acknowledging the packets of a reliable channel,
+    /// measured since the connection started or the last [`reset_ack_latency_stats`](Self::reset_ack_latency_stats).
+    /// Useful to tune the `resend_time` of the channel, the stats are empty for unreliable channels.
+    pub fn ack_latency_stats<I: Into<u8>>(&self, channel_id: I) -> AckLatencyStats {
+        self.ack_latency
+            .get(&channel_id.into())
+            .map(AckLatencyHistogram::stats)
+            .unwrap_or_default()
+    }
+
+    /// Clears the samples of [`ack_latency_stats`](Self::ack_latency_stats) for all channels.
+    pub fn reset_ack_latency_stats(&mut self) {
+        self.ack_latency.clear();
+    }
+
+    /// Returns the bytes sent and received on the channel since the connection started,
+    /// so the transport can make channel-aware send decisions, see [`get_packets_to_send_with_channel`](Self::get_packets_to_send_with_channel).
+    pub fn channel_bytes<I: Into<u8>>(&self, channel_id: I) -> ChannelBytes {
+        self.channel_bytes.get(&channel_id.into()).copied().unwrap_or_default()
+    }
+
     /// Returns whether the client is connected.
     #[inline]
     pub fn is_connected(&self) -> bool {
@@ -304,6 +592,25 @@ impl RenetClient {
         }
     }
 
+    /// Returns the ids of the send channels with queued outgoing data, in the channels priority order.
+    /// Reliable channels have pending data until all their messages are acknowledged.
+    pub fn pending_channels(&self) -> impl Iterator<Item = u8> + '_ {
+        self.channel_send_order.iter().filter_map(|order| match order {
+            ChannelOrder::Reliable(channel_id) => self.send_reliable_channels[channel_id].has_pending_data().then_some(*channel_id),
+            ChannelOrder::Unreliable(channel_id) => self.send_unreliable_channels[channel_id].has_pending_data().then_some(*channel_id),
+        })
+    }
+
+    /// Returns the number of messages dropped in an unreliable channel because its queue was full,
+    /// see [`ChannelConfig::max_unreliable_queue`]. Returns 0 for reliable channels.
+    pub fn dropped_unreliable_messages<I: Into<u8>>(&self, channel_id: I) -> u64 {
+        let channel_id = channel_id.into();
+        match self.send_unreliable_channels.get(&channel_id) {
+            Some(channel) => channel.dropped_messages(),
+            None => 0,
+        }
+    }
+
     /// Checks if the channel can send a message with the given size in bytes.
     pub fn can_send_message<I: Into<u8>>(&self, channel_id: I, size_bytes: usize) -> bool {
         let channel_id = channel_id.into();
@@ -317,14 +624,17 @@ impl RenetClient {
     }
 
     /// Send a message to the server over a channel.
+    ///
+    /// Disconnects with [`DisconnectReason::SendChannelError`] when a reliable channel is full,
+    /// use [`try_send_message`](Self::try_send_message) on channels with a [`ChannelConfig::max_in_flight`].
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
         if self.is_disconnected() {
             return;
         }
 
         let channel_id = channel_id.into();
-        if let Some(reliable_channel) = self.send_reliable_channels.get_mut(&channel_id) {
-            if let Err(error) = reliable_channel.send_message(message.into()) {
+        if self.send_reliable_channels.contains_key(&channel_id) {
+            if let Err(error) = self.send_reliable_message(channel_id, message.into(), SendChannelReliable::send_message) {
                 self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error });
             }
         } else if let Some(unreliable_channel) = self.send_unreliable_channels.get_mut(&channel_id) {
@@ -334,6 +644,77 @@ impl RenetClient {
         }
     }
 
+    /// Send a message to the server over a channel, tagged with a label for tracing.
+    ///
+    /// Messages on reliable channels are followed by a `renet_message` span until they are acked,
+    /// with events when they are queued, sent and acked. Unreliable messages only emit an event when queued.
+    /// The label is not sent over the network.
+    #[cfg(feature = "tracing")]
+    pub fn send_message_labeled<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B, label: &'static str) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        let channel_id = channel_id.into();
+        if self.send_reliable_channels.contains_key(&channel_id) {
+            let send = |channel: &mut SendChannelReliable, message| channel.send_message_labeled(message, label);
+            if let Err(error) = self.send_reliable_message(channel_id, message.into(), send) {
+                self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error });
+            }
+        } else if let Some(unreliable_channel) = self.send_unreliable_channels.get_mut(&channel_id) {
+            let message = message.into();
+            tracing::debug!(label, channel_id, len = message.len(), "renet unreliable message queued");
+            unreliable_channel.send_message(message);
+        } else {
+            panic!("Called 'send_message_labeled' with invalid channel {channel_id}");
+        }
+    }
+
+    /// Send a message to the server over a channel.
+    /// Returns an error instead of disconnecting when a reliable channel reached its [`ChannelConfig::max_in_flight`],
+    /// the message is not sent, try again after some messages were acknowledged.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), ChannelFull> {
+        if self.is_disconnected() {
+            return Ok(());
+        }
+
+        let channel_id = channel_id.into();
+        if self.send_reliable_channels.contains_key(&channel_id) {
+            match self.send_reliable_message(channel_id, message.into(), SendChannelReliable::send_message) {
+                Ok(()) => {}
+                Err(ChannelError::ReliableChannelMaxInFlightReached) => return Err(ChannelFull { channel_id }),
+                Err(error) => self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error }),
+            }
+        } else if let Some(unreliable_channel) = self.send_unreliable_channels.get_mut(&channel_id) {
+            unreliable_channel.send_message(message.into());
+        } else {
+            panic!("Called 'try_send_message' with invalid channel {channel_id}");
+        }
+
+        Ok(())
+    }
+
+    // Sends the message on the reliable channel, messages of channels without a deadline are wrapped for the global order
+    fn send_reliable_message(
+        &mut self,
+        channel_id: u8,
+        message: Bytes,
+        send: impl FnOnce(&mut SendChannelReliable, Bytes) -> Result<(), ChannelError>,
+    ) -> Result<(), ChannelError> {
+        let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
+        let global_ordered = !reliable_channel.has_deadline();
+        let message = match &self.global_order {
+            Some(global_order) if global_ordered => global_order.wrap(message),
+            _ => message,
+        };
+        send(reliable_channel, message)?;
+        if global_ordered {
+            self.global_order.iter_mut().for_each(GlobalOrder::sent);
+        }
+
+        Ok(())
+    }
+
     /// Receive a message from the server over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Bytes> {
         if self.is_disconnected() {
@@ -342,7 +723,10 @@ impl RenetClient {
 
         let channel_id = channel_id.into();
         if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
-            reliable_channel.receive_message()
+            match &mut self.global_order {
+                Some(global_order) if !reliable_channel.has_deadline() => global_order.receive_message(channel_id),
+                _ => reliable_channel.receive_message(),
+            }
         } else if let Some(unreliable_channel) = self.receive_unreliable_channels.get_mut(&channel_id) {
             unreliable_channel.receive_message()
         } else {
@@ -350,16 +734,94 @@ impl RenetClient {
         }
     }
 
+    /// Returns a message from a [`SendType::ReliableWithDeadline`] channel that was given up
+    /// since it was not acknowledged before the deadline.
+    pub fn receive_expired_message(&mut self) -> Option<ExpiredMessage> {
+        self.expired_messages.pop_front()
+    }
+
+    /// Returns the memory used by the message buffers of the channels.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for channel in self.send_reliable_channels.values() {
+            stats.reliable_send_bytes += channel.memory_usage_bytes();
+        }
+        for channel in self.send_unreliable_channels.values() {
+            stats.unreliable_send_bytes += channel.memory_usage_bytes();
+        }
+        for channel in self.receive_reliable_channels.values() {
+            let slices_bytes = channel.slices_memory_usage_bytes();
+            stats.reliable_receive_bytes += channel.memory_usage_bytes() - slices_bytes;
+            stats.slice_reassembly_bytes += slices_bytes;
+        }
+        for channel in self.receive_unreliable_channels.values() {
+            let slices_bytes = channel.slices_memory_usage_bytes();
+            stats.unreliable_receive_bytes += channel.memory_usage_bytes() - slices_bytes;
+            stats.slice_reassembly_bytes += slices_bytes;
+        }
+        if let Some(global_order) = &self.global_order {
+            stats.reliable_receive_bytes += global_order.memory_usage_bytes();
+        }
+
+        stats
+    }
+
+    /// Returns the messages received since the last update, with their arrival order and time.
+    /// Messages are logged when they are complete, before reliable channels reorder them,
+    /// duplicated reliable messages and messages dropped due to memory limits are not included.
+    /// The log is cleared on every update.
+    #[cfg(feature = "frame_log")]
+    pub fn take_frame_log(&mut self) -> Vec<FrameLogEntry> {
+        std::mem::take(&mut self.frame_log)
+    }
+
+    /// Receive all messages from the server, tagged with the channel they were received from.
+    /// Messages are returned channel by channel, following the order of the channels in the configuration.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ConnectionConfig, RenetClient};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// for (channel_id, message) in client.receive_all() {
+    ///     println!("Received message from channel {channel_id}: {message:?}");
+    /// }
+    /// ```
+    pub fn receive_all(&mut self) -> impl Iterator<Item = (u8, Bytes)> + '_ {
+        let mut channel_index = 0;
+        std::iter::from_fn(move || {
+            while let Some(&channel_id) = self.receive_channel_order.get(channel_index) {
+                if let Some(message) = self.receive_message(channel_id) {
+                    return Some((channel_id, message));
+                }
+                channel_index += 1;
+            }
+
+            None
+        })
+    }
+
     /// Advances the client by the duration.
     /// Should be called every tick
     pub fn update(&mut self, duration: Duration) {
         self.current_time += duration;
         self.stats.update(self.current_time);
+        #[cfg(feature = "frame_log")]
+        self.frame_log.clear();
 
         for unreliable_channel in self.receive_unreliable_channels.values_mut() {
             unreliable_channel.discard_incomplete_old_slices(self.current_time);
         }
 
+        for (&channel_id, reliable_channel) in self.send_reliable_channels.iter_mut() {
+            for (message_id, message) in reliable_channel.update(self.current_time) {
+                self.expired_messages.push_back(ExpiredMessage {
+                    channel_id,
+                    message_id,
+                    message,
+                });
+            }
+        }
+
         // Discard lost packets
         let mut lost_packets: Vec<u64> = Vec::new();
         for (&sequence, sent_packet) in self.sent_packets.iter() {
@@ -383,40 +845,65 @@ impl RenetClient {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn process_packet(&mut self, packet: &[u8]) {
+        let _ = self.try_process_packet(packet);
+    }
+
+    // Processes the packet and returns why it was dropped, packets are ignored once disconnected
+    pub(crate) fn try_process_packet(&mut self, packet: &[u8]) -> Result<(), ProcessError> {
         if self.is_disconnected() {
-            return;
+            return Ok(());
         }
 
         self.stats.received_packet(packet.len() as u64);
+        let packet_len = packet.len() as u64;
         let mut octets = octets::Octets::with_slice(packet);
         let packet = match Packet::from_bytes(&mut octets) {
             Err(err) => {
                 self.disconnect_with_reason(DisconnectReason::PacketDeserialization(err));
-                return;
+                return Err(ProcessError::Malformed(err));
             }
             Ok(packet) => packet,
         };
+        if let Some(channel_id) = packet.channel_id() {
+            self.channel_bytes.entry(channel_id).or_default().received += packet_len;
+        }
 
-        self.add_pending_ack(packet.sequence());
+        // Packets older than the evicted acks are processed without being acked, since their range would be evicted again.
+        // They can't be told apart from duplicates: reliable messages are deduplicated by their channel,
+        // unreliable ones are dropped so they are not received twice
+        let sequence = packet.sequence();
+        if sequence < self.evicted_acks_end {
+            self.out_of_window_packets += 1;
+            if matches!(packet, Packet::SmallUnreliable { .. } | Packet::UnreliableSlice { .. }) {
+                return Err(ProcessError::OutOfWindow);
+            }
+        } else {
+            if self.pending_acks.iter().any(|range| range.contains(&sequence)) {
+                return Err(ProcessError::Duplicate);
+            }
+            self.add_pending_ack(sequence);
+        }
 
         match packet {
             Packet::SmallReliable { channel_id, messages, .. } => {
                 let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
                     self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
+                    return Err(ProcessError::UnknownChannel(channel_id));
                 };
 
                 for (message_id, message) in messages {
                     if let Err(error) = channel.process_message(message, message_id) {
                         self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
-                        return;
+                        return Err(ProcessError::ReceiveChannel { channel_id, error });
                     }
                 }
+
+                self.process_global_order(channel_id);
             }
             Packet::SmallUnreliable { channel_id, messages, .. } => {
                 let Some(channel) = self.receive_unreliable_channels.get_mut(&channel_id) else {
                     self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
+                    return Err(ProcessError::UnknownChannel(channel_id));
                 };
 
                 for message in messages {
@@ -426,23 +913,44 @@ impl RenetClient {
             Packet::ReliableSlice { channel_id, slice, .. } => {
                 let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
                     self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
+                    return Err(ProcessError::UnknownChannel(channel_id));
                 };
 
                 if let Err(error) = channel.process_slice(slice) {
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return Err(ProcessError::ReceiveChannel { channel_id, error });
                 }
+
+                self.process_global_order(channel_id);
             }
             Packet::UnreliableSlice { channel_id, slice, .. } => {
                 let Some(channel) = self.receive_unreliable_channels.get_mut(&channel_id) else {
                     self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
+                    return Err(ProcessError::UnknownChannel(channel_id));
                 };
 
                 if let Err(error) = channel.process_slice(slice, self.current_time) {
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return Err(ProcessError::ReceiveChannel { channel_id, error });
                 }
             }
+            Packet::ConfigHash { hash, .. } => {
+                if matches!(self.config_hash, Some(config_hash) if config_hash != hash) {
+                    self.disconnect_with_reason(DisconnectReason::ConfigMismatch);
+                }
+            }
+            Packet::TimeRequest { client_time, .. } => {
+                self.time_sync.process_request(client_time, self.current_time);
+            }
+            Packet::TimeResponse {
+                client_time,
+                server_receive_time,
+                server_send_time,
+                ..
+            } => {
+                self.time_sync
+                    .process_response(client_time, server_receive_time, server_send_time, self.current_time);
+            }
             Packet::Ack { ack_ranges, .. } => {
                 // Create list with just new acks
                 // This prevents DoS from huge ack ranges
@@ -458,7 +966,8 @@ impl RenetClient {
                     self.stats.acked_packet(sent_packet.sent_at, self.current_time);
 
                     // Update rtt
-                    let rtt = (self.current_time - sent_packet.sent_at).as_secs_f64();
+                    let latency = self.current_time - sent_packet.sent_at;
+                    let rtt = latency.as_secs_f64();
                     if self.rtt < f64::EPSILON {
                         self.rtt = rtt;
                     } else {
@@ -467,6 +976,7 @@ impl RenetClient {
 
                     match sent_packet.info {
                         PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
+                            self.ack_latency.entry(channel_id).or_default().record(latency);
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
                             for message_id in message_ids {
                                 reliable_channel.process_message_ack(message_id);
@@ -477,17 +987,24 @@ impl RenetClient {
                             message_id,
                             slice_index,
                         } => {
+                            self.ack_latency.entry(channel_id).or_default().record(latency);
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
                             reliable_channel.process_slice_message_ack(message_id, slice_index);
                         }
                         PacketSentInfo::Ack { largest_acked_packet } => {
                             self.acked_largest(largest_acked_packet);
                         }
+                        PacketSentInfo::ConfigHash => self.config_hash_acked = true,
                         PacketSentInfo::None => {}
                     }
                 }
             }
         }
+
+        #[cfg(feature = "frame_log")]
+        self.log_arrived_messages();
+
+        Ok(())
     }
 
     /// Returns a list of packets to be sent to the server.
@@ -495,6 +1012,18 @@ impl RenetClient {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn get_packets_to_send(&mut self) -> Vec<Payload> {
+        self.get_packets_to_send_with_channel()
+            .into_iter()
+            .map(|(_, packet)| packet)
+            .collect()
+    }
+
+    /// Returns the packets to be sent to the server tagged with the channel of their messages,
+    /// None for acks and other control packets. Each packet only contains messages of a single channel.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn get_packets_to_send_with_channel(&mut self) -> Vec<(Option<u8>, Payload)> {
         let mut packets: Vec<Packet> = vec![];
         if self.is_disconnected() {
             return vec![];
@@ -506,6 +1035,11 @@ impl RenetClient {
                 ChannelOrder::Reliable(channel_id) => {
                     let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
                     packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes, self.current_time));
+                    if channel.retransmit_limit_reached() {
+                        let channel_id = *channel_id;
+                        self.disconnect_with_reason(DisconnectReason::ReliableTimeout { channel_id });
+                        return vec![];
+                    }
                 }
                 ChannelOrder::Unreliable(channel_id) => {
                     let channel = self.send_unreliable_channels.get_mut(channel_id).unwrap();
@@ -514,6 +1048,34 @@ impl RenetClient {
             }
         }
 
+        if let (Some(hash), false, true) = (self.config_hash, self.config_hash_acked, self.is_connected()) {
+            packets.push(Packet::ConfigHash {
+                sequence: self.packet_sequence,
+                hash,
+            });
+            self.packet_sequence += 1;
+        }
+
+        if self.is_connected() {
+            if let Some(client_time) = self.time_sync.request(self.current_time) {
+                packets.push(Packet::TimeRequest {
+                    sequence: self.packet_sequence,
+                    client_time,
+                });
+                self.packet_sequence += 1;
+            }
+        }
+
+        if let Some((client_time, server_receive_time, server_send_time)) = self.time_sync.take_response(self.current_time) {
+            packets.push(Packet::TimeResponse {
+                sequence: self.packet_sequence,
+                client_time,
+                server_receive_time,
+                server_send_time,
+            });
+            self.packet_sequence += 1;
+        }
+
         if !self.pending_acks.is_empty() {
             let ack_packet = Packet::Ack {
                 sequence: self.packet_sequence,
@@ -577,6 +1139,24 @@ impl RenetClient {
                         },
                     );
                 }
+                Packet::ConfigHash { sequence, .. } => {
+                    self.sent_packets.insert(
+                        *sequence,
+                        PacketSent {
+                            sent_at,
+                            info: PacketSentInfo::ConfigHash,
+                        },
+                    );
+                }
+                Packet::TimeRequest { sequence, .. } | Packet::TimeResponse { sequence, .. } => {
+                    self.sent_packets.insert(
+                        *sequence,
+                        PacketSent {
+                            sent_at,
+                            info: PacketSentInfo::None,
+                        },
+                    );
+                }
                 Packet::Ack { sequence, ack_ranges } => {
                     let last_range = ack_ranges.last().unwrap();
                     let largest_acked_packet = last_range.end - 1;
@@ -605,7 +1185,11 @@ impl RenetClient {
             };
 
             bytes_sent += len as u64;
-            serialized_packets.push(buffer[..len].to_vec());
+            let channel_id = packet.channel_id();
+            if let Some(channel_id) = channel_id {
+                self.channel_bytes.entry(channel_id).or_default().sent += len as u64;
+            }
+            serialized_packets.push((channel_id, buffer[..len].to_vec()));
         }
 
         self.stats.sent_packets(serialized_packets.len() as u64, bytes_sent);
@@ -613,6 +1197,60 @@ impl RenetClient {
         serialized_packets
     }
 
+    // Adds the messages that arrived in the processed packet to the frame log, in channel order.
+    #[cfg(feature = "frame_log")]
+    fn log_arrived_messages(&mut self) {
+        for &channel_id in self.receive_channel_order.iter() {
+            let arrived = match self.receive_reliable_channels.get_mut(&channel_id) {
+                Some(channel) if self.global_order.is_some() && !channel.has_deadline() => channel
+                    .take_arrived()
+                    .into_iter()
+                    .filter_map(|message| {
+                        // Remove the global sequence prefix
+                        let mut octets = octets::Octets::with_slice(&message);
+                        octets.get_varint().ok()?;
+                        Some(message.slice(octets.off()..))
+                    })
+                    .collect(),
+                Some(channel) => channel.take_arrived(),
+                None => match self.receive_unreliable_channels.get_mut(&channel_id) {
+                    Some(channel) => channel.take_arrived(),
+                    None => continue,
+                },
+            };
+
+            for message in arrived {
+                self.frame_log.push(FrameLogEntry {
+                    channel_id,
+                    order: self.frame_log.len(),
+                    received_at: self.current_time,
+                    message,
+                });
+            }
+        }
+    }
+
+    // Moves the received messages of the reliable channel to the global reorder buffer.
+    fn process_global_order(&mut self, channel_id: u8) {
+        let Some(global_order) = &mut self.global_order else {
+            return;
+        };
+        let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
+            return;
+        };
+        if channel.has_deadline() {
+            // Expired messages would leave gaps in the global sequence
+            return;
+        }
+
+        while let Some(message) = channel.receive_message() {
+            if let Err(error) = global_order.process_message(channel_id, message) {
+                self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                return;
+            }
+        }
+    }
+
     fn add_pending_ack(&mut self, sequence: u64) {
         if self.pending_acks.is_empty() {
             self.pending_acks.push(sequence..sequence + 1);
@@ -657,7 +1295,8 @@ impl RenetClient {
 
         // Limit to 64 pending ranges
         if self.pending_acks.len() > 64 {
-            self.pending_acks.remove(0);
+            let evicted = self.pending_acks.remove(0);
+            self.evicted_acks_end = self.evicted_acks_end.max(evicted.end);
         }
     }
 
@@ -687,6 +1326,87 @@ impl RenetClient {
         }
     }
 
+    // Serializes the sequence, ack and reliable channels state of the connection.
+    pub(crate) fn export_state(&self, buffer: &mut Vec<u8>) {
+        buffer.put_u64(self.packet_sequence);
+        buffer.put_u64(self.rtt.to_bits());
+        buffer.put_u64(self.pending_acks.len() as u64);
+        for ack_range in self.pending_acks.iter() {
+            buffer.put_u64(ack_range.start);
+            buffer.put_u64(ack_range.end);
+        }
+
+        let mut send_channel_ids: Vec<u8> = self.send_reliable_channels.keys().copied().collect();
+        send_channel_ids.sort_unstable();
+        buffer.put_u64(send_channel_ids.len() as u64);
+        for channel_id in send_channel_ids {
+            buffer.put_u8(channel_id);
+            self.send_reliable_channels[&channel_id].export_state(buffer);
+        }
+
+        let mut receive_channel_ids: Vec<u8> = self.receive_reliable_channels.keys().copied().collect();
+        receive_channel_ids.sort_unstable();
+        buffer.put_u64(receive_channel_ids.len() as u64);
+        for channel_id in receive_channel_ids {
+            buffer.put_u8(channel_id);
+            self.receive_reliable_channels[&channel_id].export_state(buffer);
+        }
+
+        match &self.global_order {
+            Some(global_order) => {
+                buffer.put_u8(1);
+                global_order.export_state(buffer);
+            }
+            None => buffer.put_u8(0),
+        }
+    }
+
+    // Restores the state exported by `export_state`, the connection must have been created with the same config.
+    pub(crate) fn import_state(&mut self, octets: &mut octets::Octets) -> Result<(), ImportStateError> {
+        self.packet_sequence = octets.get_u64()?;
+        self.rtt = f64::from_bits(octets.get_u64()?);
+        for _ in 0..state::get_len(octets)? {
+            let start = octets.get_u64()?;
+            let end = octets.get_u64()?;
+            if start >= end {
+                return Err(ImportStateError::InvalidState);
+            }
+            self.pending_acks.push(start..end);
+        }
+
+        let mut send_channel_ids: Vec<u8> = self.send_reliable_channels.keys().copied().collect();
+        send_channel_ids.sort_unstable();
+        if octets.get_u64()? != send_channel_ids.len() as u64 {
+            return Err(ImportStateError::ConfigMismatch);
+        }
+        for channel_id in send_channel_ids {
+            if octets.get_u8()? != channel_id {
+                return Err(ImportStateError::ConfigMismatch);
+            }
+            self.send_reliable_channels.get_mut(&channel_id).unwrap().import_state(octets)?;
+        }
+
+        let mut receive_channel_ids: Vec<u8> = self.receive_reliable_channels.keys().copied().collect();
+        receive_channel_ids.sort_unstable();
+        if octets.get_u64()? != receive_channel_ids.len() as u64 {
+            return Err(ImportStateError::ConfigMismatch);
+        }
+        for channel_id in receive_channel_ids {
+            if octets.get_u8()? != channel_id {
+                return Err(ImportStateError::ConfigMismatch);
+            }
+            self.receive_reliable_channels.get_mut(&channel_id).unwrap().import_state(octets)?;
+        }
+
+        match (&mut self.global_order, octets.get_u8()?) {
+            (Some(global_order), 1) => global_order.import_state(octets)?,
+            (None, 0) => {}
+            _ => return Err(ImportStateError::ConfigMismatch),
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn disconnect_with_reason(&mut self, reason: DisconnectReason) {
         if !self.is_disconnected() {
             self.connection_status = RenetConnectionStatus::Disconnected { reason };
@@ -752,6 +1472,230 @@ mod tests {
         assert_eq!(connection.pending_acks, vec![]);
     }
 
+    #[test]
+    fn try_send_message_channel_full() {
+        let mut channels_config = DefaultChannel::config();
+        channels_config[2].max_in_flight = Some(1);
+        let config = ConnectionConfig {
+            client_channels_config: channels_config,
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        let channel_id: u8 = DefaultChannel::ReliableOrdered.into();
+        client.try_send_message(channel_id, "first").unwrap();
+        assert_eq!(client.try_send_message(channel_id, "second"), Err(ChannelFull { channel_id }));
+        assert!(client.is_connected());
+
+        // Other channels are not affected
+        client.try_send_message(DefaultChannel::ReliableUnordered, "other").unwrap();
+    }
+
+    #[test]
+    fn receive_all() {
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        server.send_message(DefaultChannel::ReliableOrdered, "ordered");
+        server.send_message(DefaultChannel::Unreliable, "unreliable 1");
+        server.send_message(DefaultChannel::Unreliable, "unreliable 2");
+
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+
+        let messages: Vec<(u8, Bytes)> = client.receive_all().collect();
+        assert_eq!(
+            messages,
+            vec![
+                (DefaultChannel::Unreliable.into(), Bytes::from("unreliable 1")),
+                (DefaultChannel::Unreliable.into(), Bytes::from("unreliable 2")),
+                (DefaultChannel::ReliableOrdered.into(), Bytes::from("ordered")),
+            ]
+        );
+        assert!(client.receive_all().next().is_none());
+    }
+
+    #[test]
+    fn global_ordering() {
+        let config = ConnectionConfig {
+            global_ordering: true,
+            ..Default::default()
+        };
+        let mut server = RenetClient::new_from_server(config.clone());
+        let mut client = RenetClient::new(config);
+
+        server.send_message(DefaultChannel::ReliableOrdered, "first");
+        let first_packets = server.get_packets_to_send();
+        server.send_message(DefaultChannel::ReliableUnordered, "second");
+        let second_packets = server.get_packets_to_send();
+
+        // The message from the other channel waits for the previous one
+        for packet in second_packets {
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.receive_message(DefaultChannel::ReliableUnordered), None);
+
+        for packet in first_packets {
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "first");
+        assert_eq!(client.receive_message(DefaultChannel::ReliableUnordered).unwrap(), "second");
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn expired_messages() {
+        let channels_config = vec![ChannelConfig {
+            channel_id: 0,
+            max_memory_usage_bytes: 5 * 1024 * 1024,
+            send_type: SendType::ReliableWithDeadline {
+                resend_time: Duration::from_millis(100),
+                deadline: Duration::from_millis(300),
+            },
+            max_in_flight: None,
+            max_unreliable_queue: None,
+        }];
+        let config = ConnectionConfig {
+            client_channels_config: channels_config,
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        client.send_message(0, "powerup");
+        client.get_packets_to_send();
+        client.update(Duration::from_millis(300));
+
+        let expired = client.receive_expired_message().unwrap();
+        assert_eq!(
+            expired,
+            ExpiredMessage {
+                channel_id: 0,
+                message_id: 0,
+                message: Bytes::from("powerup"),
+            }
+        );
+        assert!(client.receive_expired_message().is_none());
+    }
+
+    #[test]
+    fn pending_channels() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        connection.set_connected();
+        assert_eq!(connection.pending_channels().count(), 0);
+
+        connection.send_message(DefaultChannel::Unreliable, "unreliable");
+        connection.send_message(DefaultChannel::ReliableOrdered, "reliable");
+        let pending: Vec<u8> = connection.pending_channels().collect();
+        assert_eq!(
+            pending,
+            vec![DefaultChannel::Unreliable.into(), DefaultChannel::ReliableOrdered.into()]
+        );
+
+        // Reliable messages are pending until acked
+        connection.get_packets_to_send();
+        let pending: Vec<u8> = connection.pending_channels().collect();
+        assert_eq!(pending, vec![DefaultChannel::ReliableOrdered.into()]);
+    }
+
+    #[cfg(feature = "frame_log")]
+    #[test]
+    fn frame_log() {
+        let config = ConnectionConfig {
+            global_ordering: true,
+            ..Default::default()
+        };
+        let mut server_connection = RenetClient::new_from_server(config.clone());
+        let mut client_connection = RenetClient::new(config);
+        client_connection.set_connected();
+        server_connection.set_connected();
+        client_connection.update(Duration::from_millis(100));
+
+        client_connection.send_message(DefaultChannel::ReliableUnordered, "input");
+        client_connection.send_message(DefaultChannel::Unreliable, vec![7; 3000]);
+        client_connection.send_message(DefaultChannel::ReliableOrdered, "action");
+        let packets = client_connection.get_packets_to_send();
+        server_connection.update(Duration::from_millis(100));
+        for packet in packets.iter() {
+            server_connection.process_packet(packet);
+        }
+
+        // Packets are generated in channel priority order
+        let log = server_connection.take_frame_log();
+        let received: Vec<(u8, Bytes)> = log.iter().map(|entry| (entry.channel_id, entry.message.clone())).collect();
+        assert_eq!(
+            received,
+            vec![
+                (DefaultChannel::Unreliable.into(), Bytes::from(vec![7; 3000])),
+                (DefaultChannel::ReliableUnordered.into(), Bytes::from("input")),
+                (DefaultChannel::ReliableOrdered.into(), Bytes::from("action")),
+            ]
+        );
+        assert!(log.iter().enumerate().all(|(i, entry)| entry.order == i));
+        assert!(log.iter().all(|entry| entry.received_at == Duration::from_millis(100)));
+        assert!(server_connection.take_frame_log().is_empty());
+
+        // Duplicated reliable messages are not logged again
+        for packet in packets.iter() {
+            server_connection.process_packet(packet);
+        }
+        let log = server_connection.take_frame_log();
+        assert!(log.iter().all(|entry| entry.channel_id == DefaultChannel::Unreliable.into()));
+
+        // The log is cleared on update
+        client_connection.send_message(DefaultChannel::Unreliable, "late");
+        for packet in client_connection.get_packets_to_send() {
+            server_connection.process_packet(&packet);
+        }
+        server_connection.update(Duration::from_millis(16));
+        assert!(server_connection.take_frame_log().is_empty());
+    }
+
+    #[test]
+    fn process_errors() {
+        let mut server_connection = RenetClient::new_from_server(ConnectionConfig::default());
+        let mut client_connection = RenetClient::new(ConnectionConfig::default());
+
+        client_connection.send_message(DefaultChannel::ReliableOrdered, "ping");
+        let packets = client_connection.get_packets_to_send();
+        assert_eq!(server_connection.try_process_packet(&packets[0]), Ok(()));
+        assert_eq!(server_connection.try_process_packet(&packets[0]), Err(ProcessError::Duplicate));
+        assert_eq!(server_connection.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "ping");
+        assert!(server_connection.receive_message(DefaultChannel::ReliableOrdered).is_none());
+
+        // Late packets below the evicted ack ranges are still processed
+        client_connection.send_message(DefaultChannel::ReliableOrdered, "late");
+        let late_packets = client_connection.get_packets_to_send();
+        for sequence in 0..100 {
+            server_connection.add_pending_ack(1000 + sequence * 2);
+        }
+        let pending_acks = server_connection.pending_acks.clone();
+        assert_eq!(server_connection.try_process_packet(&late_packets[0]), Ok(()));
+        assert_eq!(server_connection.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "late");
+        assert_eq!(server_connection.pending_acks, pending_acks);
+
+        // Their reliable messages are not received again
+        assert_eq!(server_connection.try_process_packet(&late_packets[0]), Ok(()));
+        assert!(server_connection.receive_message(DefaultChannel::ReliableOrdered).is_none());
+
+        // Late unreliable packets could be received twice
+        client_connection.send_message(DefaultChannel::Unreliable, "late unreliable");
+        let late_packets = client_connection.get_packets_to_send();
+        assert_eq!(
+            server_connection.try_process_packet(&late_packets[0]),
+            Err(ProcessError::OutOfWindow)
+        );
+        assert!(server_connection.receive_message(DefaultChannel::Unreliable).is_none());
+        assert_eq!(server_connection.network_info().out_of_window_packets, 3);
+
+        assert!(matches!(
+            server_connection.try_process_packet(&[255; 4]),
+            Err(ProcessError::Malformed(_))
+        ));
+        assert!(server_connection.is_disconnected());
+    }
+
     #[test]
     fn discard_old_packets() {
         let mut connection = RenetClient::new(ConnectionConfig::default());
@@ -1,11 +1,16 @@
-use crate::error::{ClientNotFound, DisconnectReason};
+use crate::channel::ExpiredMessage;
+use crate::connection_stats::{AckLatencyStats, ChannelBytes};
+use crate::error::{ChannelFull, ClientNotFound, DisconnectReason, ImportStateError, ProcessError};
 use crate::packet::Payload;
-use crate::remote_connection::{ConnectionConfig, NetworkInfo, RenetClient};
+#[cfg(feature = "frame_log")]
+use crate::remote_connection::FrameLogEntry;
+use crate::remote_connection::{ConnectionConfig, MemoryStats, NetworkInfo, RenetClient};
+use crate::state::{self, STATE_VERSION};
 use crate::ClientId;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes};
 
 /// Connection and disconnection events in the server.
 #[derive(Debug, PartialEq, Eq)]
@@ -21,6 +26,8 @@ pub struct RenetServer {
     connections: HashMap<ClientId, RenetClient>,
     connection_config: ConnectionConfig,
     events: VecDeque<ServerEvent>,
+    secure_connections: HashSet<ClientId>,
+    current_time: Duration,
 }
 
 impl RenetServer {
@@ -29,9 +36,16 @@ impl RenetServer {
             connections: HashMap::new(),
             connection_config,
             events: VecDeque::new(),
+            secure_connections: HashSet::new(),
+            current_time: Duration::ZERO,
         }
     }
 
+    /// Returns the configuration used for the connections of the server.
+    pub fn connection_config(&self) -> &ConnectionConfig {
+        &self.connection_config
+    }
+
     /// Adds a new connection to the server. If a connection already exits it does nothing.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
@@ -42,12 +56,43 @@ impl RenetServer {
         }
 
         let mut connection = RenetClient::new_from_server(self.connection_config.clone());
+        connection.set_server_time_base(self.current_time);
         // Consider newly added connections as connected
         connection.set_connected();
         self.connections.insert(client_id, connection);
         self.events.push_back(ServerEvent::ClientConnected { client_id })
     }
 
+    /// Marks whether the transport reported an encrypted path for the connection.
+    /// It does nothing if the client does not exits.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn set_secure(&mut self, client_id: ClientId, secure: bool) {
+        if !self.connections.contains_key(&client_id) {
+            return;
+        }
+
+        if secure {
+            self.secure_connections.insert(client_id);
+        } else {
+            self.secure_connections.remove(&client_id);
+        }
+    }
+
+    /// Returns whether the connection with the client is encrypted.
+    /// Returns false if the client is not found or the transport did not report the connection as secure.
+    ///
+    /// How each transport determines it:
+    /// - `renet_netcode`: secure when the server uses `ServerAuthentication::Secure`,
+    ///   unsecure connections are encrypted with a known key.
+    /// - `renet_steam`: always secure, steam networking sockets encrypt every connection
+    ///   unless the `Unencrypted` config value is passed in the socket options.
+    /// - Local clients created with [`Self::new_local_client`] are never reported as secure.
+    pub fn is_secure(&self, client_id: ClientId) -> bool {
+        self.secure_connections.contains(&client_id)
+    }
+
     /// Returns a server event if available
     ///
     /// # Usage
@@ -123,12 +168,55 @@ impl RenetServer {
         }
     }
 
+    /// Returns the ack latency of a reliable channel of the client, see [`RenetClient::ack_latency_stats`].
+    pub fn ack_latency_stats<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> Result<AckLatencyStats, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.ack_latency_stats(channel_id)),
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns the bytes sent to and received from the client on the channel, see [`RenetClient::channel_bytes`].
+    pub fn channel_bytes<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> Result<ChannelBytes, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.channel_bytes(channel_id)),
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Clears the ack latency samples of all clients, see [`RenetClient::reset_ack_latency_stats`].
+    pub fn reset_ack_latency_stats(&mut self) {
+        for connection in self.connections.values_mut() {
+            connection.reset_ack_latency_stats();
+        }
+    }
+
+    /// Returns the memory used by the message buffers of the client, see [`RenetClient::memory_stats`].
+    pub fn client_memory_stats(&self, client_id: ClientId) -> Result<MemoryStats, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.memory_stats()),
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns the memory used by the message buffers of all connections,
+    /// useful to budget the memory of server instances.
+    /// The memory of each connection is bounded by the `max_memory_usage_bytes` of its channels.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for connection in self.connections.values() {
+            stats += connection.memory_stats();
+        }
+        stats
+    }
+
     /// Removes a connection from the server, emits an disconnect server event.
     /// It does nothing if the client does not exits.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn remove_connection(&mut self, client_id: ClientId) {
+        self.secure_connections.remove(&client_id);
         if let Some(connection) = self.connections.remove(&client_id) {
             let reason = connection.disconnect_reason().unwrap_or(DisconnectReason::Transport);
             self.events.push_back(ServerEvent::ClientDisconnected { client_id, reason });
@@ -150,6 +238,10 @@ impl RenetServer {
     }
 
     /// Send a message to all clients over a channel.
+    ///
+    /// The message is converted into [`Bytes`] once and the same buffer is shared by the channels of all connections,
+    /// only the packet framing (sequences, message ids and slice headers) is serialized for each connection.
+    /// Prefer it over calling [`Self::send_message`] with a copy of the payload for each client.
     pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
         let channel_id = channel_id.into();
         let message = message.into();
@@ -171,6 +263,41 @@ impl RenetServer {
         }
     }
 
+    /// Relays a payload received from a client to another client over a channel.
+    /// The payload is forwarded as is, without being copied or interpreted by the server,
+    /// so it can be a message received with [`Self::receive_message`].
+    /// Returns an error if the sender or the receiver is not connected.
+    pub fn relay<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        from: ClientId,
+        to: ClientId,
+        channel_id: I,
+        payload: B,
+    ) -> Result<(), ClientNotFound> {
+        if !self.is_connected(from) || !self.is_connected(to) {
+            return Err(ClientNotFound);
+        }
+
+        self.send_message(to, channel_id, payload);
+        Ok(())
+    }
+
+    /// Relays a payload received from a client to all other connected clients over a channel, see [`Self::relay`].
+    /// Returns an error if the sender is not connected.
+    pub fn relay_broadcast<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        from: ClientId,
+        channel_id: I,
+        payload: B,
+    ) -> Result<(), ClientNotFound> {
+        if !self.is_connected(from) {
+            return Err(ClientNotFound);
+        }
+
+        self.broadcast_message_except(from, channel_id, payload);
+        Ok(())
+    }
+
     /// Returns the available memory in bytes of a channel for the given client.
     /// Returns 0 if the client is not found.
     pub fn channel_available_memory<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> usize {
@@ -180,6 +307,24 @@ impl RenetServer {
         }
     }
 
+    /// Returns the number of messages dropped for the client in an unreliable channel because its queue was full,
+    /// see [`RenetClient::dropped_unreliable_messages`]. Returns 0 if the client is not found.
+    pub fn dropped_unreliable_messages<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> u64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.dropped_unreliable_messages(channel_id),
+            None => 0,
+        }
+    }
+
+    /// Returns the ids of the channels with queued outgoing data for the client, see [`RenetClient::pending_channels`].
+    /// Returns an empty iterator if the client is not found.
+    pub fn pending_channels(&self, client_id: ClientId) -> impl Iterator<Item = u8> + '_ {
+        self.connections
+            .get(&client_id)
+            .into_iter()
+            .flat_map(|connection| connection.pending_channels())
+    }
+
     /// Checks if can send a message with the given size in bytes over a channel for the given client.
     /// Returns false if the client is not found.
     pub fn can_send_message<I: Into<u8>>(&self, client_id: ClientId, channel_id: I, size_bytes: usize) -> bool {
@@ -190,6 +335,8 @@ impl RenetServer {
     }
 
     /// Send a message to a client over a channel.
+    ///
+    /// Disconnects the client when a reliable channel is full, see [`RenetClient::send_message`].
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, message: B) {
         match self.connections.get_mut(&client_id) {
             Some(connection) => connection.send_message(channel_id, message),
@@ -197,6 +344,37 @@ impl RenetServer {
         }
     }
 
+    /// Send a message to a client over a channel, tagged with a label for tracing, see [`RenetClient::send_message_labeled`].
+    #[cfg(feature = "tracing")]
+    pub fn send_message_labeled<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        message: B,
+        label: &'static str,
+    ) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send_message_labeled(channel_id, message, label),
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Send a message to a client over a channel, see [`RenetClient::try_send_message`].
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        message: B,
+    ) -> Result<(), ChannelFull> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.try_send_message(channel_id, message),
+            None => {
+                log::error!("Tried to send a message to invalid client {:?}", client_id);
+                Ok(())
+            }
+        }
+    }
+
     /// Receive a message from a client over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<Bytes> {
         if let Some(connection) = self.connections.get_mut(&client_id) {
@@ -205,6 +383,22 @@ impl RenetServer {
         None
     }
 
+    /// Returns a message from a [`SendType::ReliableWithDeadline`][crate::SendType::ReliableWithDeadline] channel
+    /// that was given up for the client, see [`RenetClient::receive_expired_message`].
+    pub fn receive_expired_message(&mut self, client_id: ClientId) -> Option<ExpiredMessage> {
+        self.connections.get_mut(&client_id)?.receive_expired_message()
+    }
+
+    /// Returns the messages received from the client since the last update, see [`RenetClient::take_frame_log`].
+    /// Returns an empty log if the client is not found.
+    #[cfg(feature = "frame_log")]
+    pub fn take_frame_log(&mut self, client_id: ClientId) -> Vec<FrameLogEntry> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.take_frame_log(),
+            None => vec![],
+        }
+    }
+
     /// Return ids for all connected clients (iterator)
     pub fn clients_id_iter(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.iter().filter(|(_, c)| c.is_connected()).map(|(id, _)| *id)
@@ -238,9 +432,16 @@ impl RenetServer {
         false
     }
 
+    /// Returns the server clock, the sum of all durations passed to [`Self::update`].
+    /// Clients estimate it with [`RenetClient::estimated_server_time`].
+    pub fn current_time(&self) -> Duration {
+        self.current_time
+    }
+
     /// Advances the server by the duration.
     /// Should be called every tick
     pub fn update(&mut self, duration: Duration) {
+        self.current_time += duration;
         for connection in self.connections.values_mut() {
             connection.update(duration);
         }
@@ -257,20 +458,94 @@ impl RenetServer {
         }
     }
 
-    /// Process a packet received from the client.
+    /// Returns the packets to be sent to the client tagged with the channel of their messages,
+    /// see [`RenetClient::get_packets_to_send_with_channel`].
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
-    pub fn process_packet_from(&mut self, payload: &[u8], client_id: ClientId) -> Result<(), ClientNotFound> {
+    pub fn get_packets_to_send_with_channel(&mut self, client_id: ClientId) -> Result<Vec<(Option<u8>, Payload)>, ClientNotFound> {
         match self.connections.get_mut(&client_id) {
-            Some(connection) => {
-                connection.process_packet(payload);
-                Ok(())
-            }
+            Some(connection) => Ok(connection.get_packets_to_send_with_channel()),
             None => Err(ClientNotFound),
         }
     }
 
+    /// Process a packet received from the client, returns why the packet was dropped, see [`ProcessError`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn process_packet_from(&mut self, payload: &[u8], client_id: ClientId) -> Result<(), ProcessError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.try_process_packet(payload),
+            None => Err(ProcessError::ClientNotFound),
+        }
+    }
+
+    /// Serializes the state of all connected clients, to be restored with [`Self::import_state`]
+    /// after reloading the game logic or in a new process, without resetting the connections.
+    ///
+    /// The state keeps the packet sequences, pending acks, rtt and reliable channels
+    /// (messages waiting for acknowledgement, received messages and partially received slices).
+    /// Messages that were sent but not acknowledged are resent after importing,
+    /// queued unreliable messages and the connection stats are discarded.
+    /// Messages of [`SendType::ReliableWithDeadline`][crate::SendType::ReliableWithDeadline] channels sent before exporting no longer expire.
+    ///
+    /// The transport connections are not part of the state, they must be handed off separately.
+    ///
+    /// # Version compatibility
+    /// The state can only be imported by the same version of renet, the format is versioned and
+    /// importing a state from a different format returns [`ImportStateError::UnsupportedVersion`].
+    /// Both servers must also use the same [`ConnectionConfig`], the channels are checked when importing.
+    pub fn export_state(&self) -> Vec<u8> {
+        let mut buffer = vec![STATE_VERSION];
+        let connections: Vec<(&ClientId, &RenetClient)> = self.connections.iter().filter(|(_, c)| c.is_connected()).collect();
+        buffer.put_u64(connections.len() as u64);
+        for (&client_id, connection) in connections {
+            buffer.put_u64(client_id);
+            buffer.put_u8(self.secure_connections.contains(&client_id) as u8);
+            connection.export_state(&mut buffer);
+        }
+
+        buffer
+    }
+
+    /// Restores the clients from a state exported with [`Self::export_state`].
+    /// Existing connections with the same client ids are replaced, no server events are emitted.
+    /// Nothing is imported if the state is invalid.
+    pub fn import_state(&mut self, state: &[u8]) -> Result<(), ImportStateError> {
+        let mut octets = octets::Octets::with_slice(state);
+        let version = octets.get_u8()?;
+        if version != STATE_VERSION {
+            return Err(ImportStateError::UnsupportedVersion { version });
+        }
+
+        let mut clients: Vec<(ClientId, bool, RenetClient)> = Vec::new();
+        for _ in 0..state::get_len(&mut octets)? {
+            let client_id = octets.get_u64()?;
+            let secure = octets.get_u8()? != 0;
+            let mut connection = RenetClient::new_from_server(self.connection_config.clone());
+            connection.set_server_time_base(self.current_time);
+            connection.set_connected();
+            connection.import_state(&mut octets)?;
+            clients.push((client_id, secure, connection));
+        }
+
+        if octets.cap() != 0 {
+            return Err(ImportStateError::InvalidState);
+        }
+
+        for (client_id, secure, connection) in clients {
+            self.connections.insert(client_id, connection);
+            if secure {
+                self.secure_connections.insert(client_id);
+            } else {
+                self.secure_connections.remove(&client_id);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Creates a local [RenetClient], use this for testing.
     /// Use [`Self::process_local_client`] to update the local connection.
     pub fn new_local_client(&mut self, client_id: ClientId) -> RenetClient {
@@ -289,6 +564,7 @@ impl RenetServer {
         }
         client.disconnect();
 
+        self.secure_connections.remove(&client_id);
         if self.connections.remove(&client_id).is_some() {
             self.events.push_back(ServerEvent::ClientDisconnected {
                 client_id,
@@ -305,7 +581,10 @@ impl RenetServer {
         }
 
         for packet in client.get_packets_to_send() {
-            self.process_packet_from(&packet, client_id)?
+            // Packets of local clients are not lost, the other errors disconnect the client
+            if let Err(ProcessError::ClientNotFound) = self.process_packet_from(&packet, client_id) {
+                return Err(ClientNotFound);
+            }
         }
 
         Ok(())
@@ -0,0 +1,32 @@
+// Helpers to serialize the connection state, used by RenetServer::export_state and import_state
+use bytes::{BufMut, Bytes};
+use octets::Octets;
+
+use crate::error::ImportStateError;
+
+/// Version of the serialized state format, bumped on any change of the format.
+pub(crate) const STATE_VERSION: u8 = 1;
+
+pub(crate) fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.put_u64(bytes.len() as u64);
+    buffer.put_slice(bytes);
+}
+
+pub(crate) fn get_bytes(octets: &mut Octets) -> Result<Bytes, ImportStateError> {
+    let len = octets.get_u64()? as usize;
+    if len > octets.cap() {
+        return Err(ImportStateError::InvalidState);
+    }
+
+    Ok(Bytes::copy_from_slice(octets.get_bytes(len)?.buf()))
+}
+
+// Number of entries to read, checked with the remaining bytes so invalid states can't allocate too much
+pub(crate) fn get_len(octets: &mut Octets) -> Result<usize, ImportStateError> {
+    let len = octets.get_u64()? as usize;
+    if len > octets.cap() {
+        return Err(ImportStateError::InvalidState);
+    }
+
+    Ok(len)
+}
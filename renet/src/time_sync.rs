@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, time::Duration};
+
+// Number of samples kept to estimate the server clock
+const SAMPLES: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    round_trip: u64,
+    // Offset of the server clock to the client clock in microseconds
+    offset: i64,
+}
+
+// Estimates the server clock from request/response timestamps, similar to NTP.
+// The client side sends requests every interval, the server side answers them with its clock.
+#[derive(Debug)]
+pub struct TimeSync {
+    interval: Option<Duration>,
+    last_request_at: Option<Duration>,
+    samples: VecDeque<Sample>,
+    // Server side: the server clock when the connection was created
+    server_time_base: Duration,
+    // Server side: the most recent request waiting for a response, (client_time, server_receive_time)
+    pending_response: Option<(u64, u64)>,
+}
+
+impl TimeSync {
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last_request_at: None,
+            samples: VecDeque::with_capacity(SAMPLES),
+            server_time_base: Duration::ZERO,
+            pending_response: None,
+        }
+    }
+
+    pub fn set_server_time_base(&mut self, server_time_base: Duration) {
+        self.server_time_base = server_time_base;
+    }
+
+    fn server_time(&self, current_time: Duration) -> u64 {
+        (self.server_time_base + current_time).as_micros() as u64
+    }
+
+    // Returns the client time to send in a request, if a request should be sent.
+    pub fn request(&mut self, current_time: Duration) -> Option<u64> {
+        let interval = self.interval?;
+        if matches!(self.last_request_at, Some(last_request_at) if current_time - last_request_at < interval) {
+            return None;
+        }
+
+        self.last_request_at = Some(current_time);
+        Some(current_time.as_micros() as u64)
+    }
+
+    pub fn process_request(&mut self, client_time: u64, current_time: Duration) {
+        self.pending_response = Some((client_time, self.server_time(current_time)));
+    }
+
+    // Returns the response to send, (client_time, server_receive_time, server_send_time).
+    pub fn take_response(&mut self, current_time: Duration) -> Option<(u64, u64, u64)> {
+        let (client_time, server_receive_time) = self.pending_response.take()?;
+        Some((client_time, server_receive_time, self.server_time(current_time)))
+    }
+
+    pub fn process_response(&mut self, client_time: u64, server_receive_time: u64, server_send_time: u64, current_time: Duration) {
+        let client_receive_time = current_time.as_micros() as u64;
+        if client_receive_time < client_time || server_send_time < server_receive_time {
+            return;
+        }
+
+        let server_processing = server_send_time - server_receive_time;
+        let round_trip = (client_receive_time - client_time).saturating_sub(server_processing);
+        let offset = ((server_receive_time as i64 - client_time as i64) + (server_send_time as i64 - client_receive_time as i64)) / 2;
+
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample { round_trip, offset });
+    }
+
+    // The sample with the smallest round trip is the least affected by jitter
+    pub fn offset(&self) -> Option<f64> {
+        let sample = self.samples.iter().min_by_key(|sample| sample.round_trip)?;
+        Some(sample.offset as f64 / 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_offset() {
+        let mut client = TimeSync::new(Some(Duration::from_secs(1)));
+        let mut server = TimeSync::new(None);
+        // Server clock is 10s ahead
+        server.set_server_time_base(Duration::from_secs(10));
+
+        let client_time = client.request(Duration::ZERO).unwrap();
+        assert_eq!(client.request(Duration::from_millis(500)), None);
+
+        // 50ms of latency each way, 16ms until the server sends the response
+        server.process_request(client_time, Duration::from_millis(50));
+        let (client_time, receive_time, send_time) = server.take_response(Duration::from_millis(66)).unwrap();
+        client.process_response(client_time, receive_time, send_time, Duration::from_millis(116));
+        assert_eq!(client.offset(), Some(10.0));
+
+        // A sample with more jitter is ignored
+        let client_time = client.request(Duration::from_secs(1)).unwrap();
+        server.process_request(client_time, Duration::from_millis(1200));
+        let (client_time, receive_time, send_time) = server.take_response(Duration::from_millis(1200)).unwrap();
+        client.process_response(client_time, receive_time, send_time, Duration::from_millis(1250));
+        assert_eq!(client.offset(), Some(10.0));
+    }
+
+    #[test]
+    fn disabled() {
+        let mut client = TimeSync::new(None);
+        assert_eq!(client.request(Duration::ZERO), None);
+        assert_eq!(client.offset(), None);
+    }
+}
@@ -1,5 +1,9 @@
 use bytes::Bytes;
-use renet::{ClientId, ConnectionConfig, DefaultChannel, DisconnectReason, RenetClient, RenetServer, ServerEvent};
+use renet::{
+    AckLatencyStats, ChannelBytes, ChannelConfig, ClientId, ConnectionConfig, DefaultChannel, DisconnectReason, ImportStateError,
+    MemoryStats, RenetClient, RenetServer, SendType, ServerEvent,
+};
+use std::time::Duration;
 
 pub fn init_log() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -102,3 +106,449 @@ fn test_local_client() {
             }
     );
 }
+
+#[test]
+fn test_secure_connection() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let client_id: ClientId = 0;
+    // Cannot mark unknown clients as secure
+    server.set_secure(client_id, true);
+    assert!(!server.is_secure(client_id));
+
+    server.add_connection(client_id);
+    assert!(!server.is_secure(client_id));
+
+    server.set_secure(client_id, true);
+    assert!(server.is_secure(client_id));
+
+    server.remove_connection(client_id);
+    assert!(!server.is_secure(client_id));
+}
+
+#[test]
+fn test_connection_config() {
+    let config = ConnectionConfig {
+        available_bytes_per_tick: 1200,
+        ..Default::default()
+    };
+    let server = RenetServer::new(config);
+
+    let config = server.connection_config();
+    assert_eq!(config.available_bytes_per_tick, 1200);
+    assert_eq!(config.server_channels_config.len(), DefaultChannel::config().len());
+}
+
+#[test]
+fn test_export_import_state() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    server.set_secure(client_id, true);
+
+    // Unacked messages in both directions, including a sliced one
+    let sliced_message = Bytes::from("test".repeat(1000));
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "first");
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, sliced_message.clone());
+    client.send_message(DefaultChannel::ReliableOrdered, "from client");
+    let lost_packets = server.get_packets_to_send(client_id).unwrap();
+    assert!(!lost_packets.is_empty());
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    let state = server.export_state();
+    let mut new_server = RenetServer::new(ConnectionConfig::default());
+    new_server.import_state(&state).unwrap();
+    assert!(new_server.is_connected(client_id));
+    assert!(new_server.is_secure(client_id));
+    assert!(new_server.get_event().is_none());
+
+    // Messages received before exporting are kept
+    assert_eq!(
+        new_server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "from client"
+    );
+
+    // Unacked messages are resent by the new server
+    new_server.send_message(client_id, DefaultChannel::ReliableOrdered, "second");
+    for packet in new_server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "first");
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), sliced_message);
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "second");
+    assert!(client.is_connected());
+
+    // The client resends its message since the ack was never sent, it's not received twice
+    client.update(std::time::Duration::from_secs(1));
+    for packet in client.get_packets_to_send() {
+        new_server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert!(new_server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_none());
+}
+
+#[test]
+fn test_import_invalid_state() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.add_connection(0);
+    let mut state = server.export_state();
+
+    let mut new_server = RenetServer::new(ConnectionConfig {
+        server_channels_config: vec![DefaultChannel::config()[0].clone()],
+        ..Default::default()
+    });
+    assert_eq!(new_server.import_state(&state), Err(ImportStateError::ConfigMismatch));
+
+    let mut new_server = RenetServer::new(ConnectionConfig::default());
+    assert_eq!(
+        new_server.import_state(&state[..state.len() - 1]),
+        Err(ImportStateError::InvalidState)
+    );
+
+    state[0] = u8::MAX;
+    assert_eq!(
+        new_server.import_state(&state),
+        Err(ImportStateError::UnsupportedVersion { version: u8::MAX })
+    );
+    assert!(!new_server.has_connections());
+}
+
+#[test]
+fn test_time_sync() {
+    init_log();
+    let config = ConnectionConfig {
+        time_sync_interval: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    client.set_connected();
+
+    // The server started 10 seconds before the client
+    server.update(Duration::from_secs(10));
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(client.estimated_server_time_offset_secs(), None);
+
+    for _ in 0..10 {
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+        server.update(Duration::from_millis(16));
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+        client.update(Duration::from_millis(16));
+    }
+
+    let offset = client.estimated_server_time_offset_secs().unwrap();
+    assert!((offset - 10.0).abs() <= 0.016, "offset {offset}");
+    let server_time = client.estimated_server_time().unwrap();
+    assert!(server_time.abs_diff(server.current_time()) <= Duration::from_millis(16));
+}
+
+#[test]
+fn test_relay() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client_a = server.new_local_client(0);
+    let mut client_b = server.new_local_client(1);
+    let mut client_c = server.new_local_client(2);
+
+    client_a.send_message(DefaultChannel::Unreliable, "voice");
+    server.process_local_client(0, &mut client_a).unwrap();
+    let payload = server.receive_message(0, DefaultChannel::Unreliable).unwrap();
+
+    server.relay(0, 1, DefaultChannel::Unreliable, payload.clone()).unwrap();
+    server.relay_broadcast(0, DefaultChannel::ReliableOrdered, payload).unwrap();
+    assert!(server.relay(0, 3, DefaultChannel::Unreliable, "voice").is_err());
+    assert!(server.relay_broadcast(3, DefaultChannel::Unreliable, "voice").is_err());
+
+    for (client_id, client) in [(0, &mut client_a), (1, &mut client_b), (2, &mut client_c)] {
+        server.process_local_client(client_id, client).unwrap();
+    }
+    assert_eq!(client_b.receive_message(DefaultChannel::Unreliable).unwrap(), "voice");
+    assert_eq!(client_b.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "voice");
+    assert_eq!(client_c.receive_message(DefaultChannel::Unreliable), None);
+    assert_eq!(client_c.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "voice");
+    assert_eq!(client_a.receive_message(DefaultChannel::ReliableOrdered), None);
+}
+
+#[test]
+fn test_validate_config() {
+    init_log();
+    let config = ConnectionConfig {
+        validate_config: true,
+        ..Default::default()
+    };
+    let mut mismatched_config = config.clone();
+    mismatched_config.server_channels_config.push(ChannelConfig {
+        channel_id: 3,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::Unreliable,
+        max_in_flight: None,
+        max_unreliable_queue: None,
+    });
+    let global_ordering_config = ConnectionConfig {
+        global_ordering: true,
+        ..config.clone()
+    };
+    assert_eq!(config.config_hash(), config.clone().config_hash());
+    assert_ne!(config.config_hash(), mismatched_config.config_hash());
+    assert_ne!(config.config_hash(), global_ordering_config.config_hash());
+
+    for (server_config, expected_reason) in [
+        (config.clone(), None),
+        (mismatched_config, Some(DisconnectReason::ConfigMismatch)),
+        (global_ordering_config, Some(DisconnectReason::ConfigMismatch)),
+    ] {
+        let mut server = RenetServer::new(server_config);
+        let mut client = RenetClient::new(config.clone());
+        client.set_connected();
+        let client_id: ClientId = 0;
+        server.add_connection(client_id);
+
+        for _ in 0..3 {
+            for packet in client.get_packets_to_send() {
+                let _ = server.process_packet_from(&packet, client_id);
+            }
+            server.update(Duration::from_millis(16));
+            if let Ok(packets) = server.get_packets_to_send(client_id) {
+                for packet in packets {
+                    client.process_packet(&packet);
+                }
+            }
+            client.update(Duration::from_millis(16));
+        }
+
+        // The server receives the client hash first, the transport then disconnects the client
+        assert_eq!(server.is_connected(client_id), expected_reason.is_none());
+        assert!(client.is_connected());
+        if let Some(reason) = expected_reason {
+            assert_eq!(server.disconnections_id(), vec![client_id]);
+            server.remove_connection(client_id);
+            assert_eq!(server.get_event(), Some(ServerEvent::ClientConnected { client_id }));
+            assert_eq!(server.get_event(), Some(ServerEvent::ClientDisconnected { client_id, reason }));
+        }
+    }
+}
+
+#[test]
+fn test_memory_stats() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    server.add_connection(1);
+    assert_eq!(server.memory_stats(), MemoryStats::default());
+
+    server.broadcast_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+    server.broadcast_message(DefaultChannel::Unreliable, vec![0; 10]);
+    let stats = server.memory_stats();
+    assert_eq!(stats.reliable_send_bytes, 200);
+    assert_eq!(stats.unreliable_send_bytes, 20);
+    assert_eq!(server.client_memory_stats(client_id).unwrap().total_bytes(), 110);
+    assert!(server.client_memory_stats(2).is_err());
+
+    client.send_message(DefaultChannel::Unreliable, vec![0; 10]);
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    // Only the first slice of a sliced message is received
+    client.send_message(DefaultChannel::ReliableOrdered, vec![0; 3000]);
+    let packets = client.get_packets_to_send();
+    server.process_packet_from(&packets[0], client_id).unwrap();
+    let stats = server.client_memory_stats(client_id).unwrap();
+    assert!(stats.slice_reassembly_bytes >= 3000);
+    assert_eq!(stats.unreliable_receive_bytes, 10);
+    assert_eq!(stats.reliable_receive_bytes, 0);
+}
+
+#[test]
+fn test_deterministic_packets() {
+    let run = || {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+        server.add_connection(0);
+
+        let mut sent = vec![];
+        for tick in 0..10u8 {
+            client.send_message(DefaultChannel::ReliableOrdered, vec![tick; 2000]);
+            client.send_message(DefaultChannel::Unreliable, vec![tick]);
+            server.send_message(0, DefaultChannel::ReliableUnordered, vec![tick; 10]);
+
+            for packet in client.get_packets_to_send() {
+                server.process_packet_from(&packet, 0).unwrap();
+                sent.push(packet);
+            }
+            for packet in server.get_packets_to_send(0).unwrap() {
+                client.process_packet(&packet);
+                sent.push(packet);
+            }
+            client.update(Duration::from_millis(16));
+            server.update(Duration::from_millis(16));
+        }
+        sent
+    };
+
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_validate_packet_size() {
+    let config = ConnectionConfig::default();
+    assert!(config.validate_packet_size(1300).is_ok());
+
+    let error = config.validate_packet_size(1000).unwrap_err();
+    assert_eq!(error.max_packet_size, config.max_packet_size());
+    assert_eq!(error.transport_max_packet_size, 1000);
+}
+
+#[test]
+fn test_reliable_timeout() {
+    assert_eq!(ConnectionConfig::default().reliable_timeout(), None);
+
+    // The default reliable channels resend every 300ms
+    let config = ConnectionConfig {
+        max_retransmits: Some(9),
+        ..Default::default()
+    };
+    assert_eq!(config.reliable_timeout(), Some(Duration::from_secs(3)));
+
+    // Channels resending on every update don't shorten the timeout
+    let mut config = config;
+    config.server_channels_config.push(ChannelConfig {
+        channel_id: 3,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: Duration::ZERO,
+        },
+        max_in_flight: None,
+        max_unreliable_queue: None,
+    });
+    assert_eq!(config.reliable_timeout(), Some(Duration::from_secs(3)));
+
+    config.server_channels_config.retain(|channel| channel.channel_id == 3);
+    config.client_channels_config.clear();
+    assert_eq!(config.reliable_timeout(), None);
+}
+
+#[test]
+fn test_max_retransmits() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig {
+        max_retransmits: Some(3),
+        ..Default::default()
+    });
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(server.get_event(), Some(ServerEvent::ClientConnected { client_id }));
+
+    // The client never acknowledges the message
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "lost");
+    for _ in 0..10 {
+        server.update(Duration::from_secs(1));
+        let _ = server.get_packets_to_send(client_id);
+    }
+
+    assert_eq!(server.disconnections_id(), vec![client_id]);
+    assert_eq!(server.network_info(client_id).unwrap().retransmits, 3);
+    server.remove_connection(client_id);
+    assert_eq!(
+        server.get_event(),
+        Some(ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::ReliableTimeout {
+                channel_id: DefaultChannel::ReliableOrdered.into()
+            }
+        })
+    );
+}
+
+#[test]
+fn test_ack_latency_stats() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "ping");
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+
+    // The client acknowledges the packet 50ms later
+    server.update(Duration::from_millis(50));
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    let stats = server.ack_latency_stats(client_id, DefaultChannel::ReliableOrdered).unwrap();
+    assert_eq!(stats.samples, 1);
+    assert_eq!(stats.min, Duration::from_millis(50));
+    assert_eq!(stats.p95, Duration::from_millis(50));
+    assert_eq!(
+        server.ack_latency_stats(client_id, DefaultChannel::Unreliable).unwrap(),
+        AckLatencyStats::default()
+    );
+
+    server.reset_ack_latency_stats();
+    assert_eq!(
+        server
+            .ack_latency_stats(client_id, DefaultChannel::ReliableOrdered)
+            .unwrap()
+            .samples,
+        0
+    );
+    assert!(server.ack_latency_stats(1, DefaultChannel::ReliableOrdered).is_err());
+}
+
+#[test]
+fn test_channel_bytes() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![0; 100]);
+    server.send_message(client_id, DefaultChannel::Unreliable, vec![0; 20]);
+    let packets = server.get_packets_to_send_with_channel(client_id).unwrap();
+    assert_eq!(packets.len(), 2);
+
+    let reliable: u8 = DefaultChannel::ReliableOrdered.into();
+    let mut reliable_bytes = 0;
+    for (channel_id, packet) in packets.iter() {
+        if *channel_id == Some(reliable) {
+            reliable_bytes += packet.len() as u64;
+        }
+        client.process_packet(packet);
+    }
+    let sent = server.channel_bytes(client_id, DefaultChannel::ReliableOrdered).unwrap();
+    assert_eq!(sent.sent, reliable_bytes);
+    assert_eq!(client.channel_bytes(DefaultChannel::ReliableOrdered).received, reliable_bytes);
+
+    // Acks are not counted in any channel
+    let packets = client.get_packets_to_send_with_channel();
+    assert!(packets.iter().all(|(channel_id, _)| channel_id.is_none()));
+    for (_, packet) in packets {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(
+        server.channel_bytes(client_id, DefaultChannel::ReliableOrdered).unwrap().received,
+        0
+    );
+    assert_eq!(client.channel_bytes(DefaultChannel::ReliableUnordered), ChannelBytes::default());
+    assert!(server.channel_bytes(1, DefaultChannel::ReliableOrdered).is_err());
+}
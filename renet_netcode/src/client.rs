@@ -4,9 +4,9 @@ use std::{
     time::Duration,
 };
 
-use renetcode::{ClientAuthentication, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES};
+use renetcode::{ClientAuthentication, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES};
 
-use renet::{ClientId, RenetClient};
+use renet::{ClientId, ConnectionConfig, PacketSizeError, RenetClient};
 
 use super::NetcodeTransportError;
 
@@ -38,6 +38,17 @@ impl NetcodeClientTransport {
         self.netcode_client.client_id()
     }
 
+    /// Returns the maximum size in bytes of the renet packets the transport can send.
+    pub fn max_packet_size(&self) -> usize {
+        NETCODE_MAX_PAYLOAD_BYTES
+    }
+
+    /// Checks that the packets generated with the connection config fit in the transport,
+    /// see [`ConnectionConfig::validate_packet_size`].
+    pub fn validate_packet_size(&self, connection_config: &ConnectionConfig) -> Result<(), PacketSizeError> {
+        connection_config.validate_packet_size(self.max_packet_size())
+    }
+
     /// Returns the duration since the client last received a packet.
     /// Usefull to detect timeouts.
     pub fn time_since_last_received_packet(&self) -> Duration {
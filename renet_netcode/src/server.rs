@@ -4,10 +4,13 @@ use std::{
     time::Duration,
 };
 
-use renetcode::{NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
+use renetcode::{
+    NetcodeServer, ServerAuthentication, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES,
+    NETCODE_USER_DATA_BYTES,
+};
 
 use renet::ClientId;
-use renet::RenetServer;
+use renet::{ConnectionConfig, PacketSizeError, ProcessError, RenetServer};
 
 use super::NetcodeTransportError;
 
@@ -17,18 +20,22 @@ pub struct NetcodeServerTransport {
     socket: UdpSocket,
     netcode_server: NetcodeServer,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    secure: bool,
 }
 
 impl NetcodeServerTransport {
     pub fn new(server_config: ServerConfig, socket: UdpSocket) -> Result<Self, std::io::Error> {
         socket.set_nonblocking(true)?;
 
+        // Unsecure connections are encrypted with a known key, so they are not reported as secure
+        let secure = matches!(server_config.authentication, ServerAuthentication::Secure { .. });
         let netcode_server = NetcodeServer::new(server_config);
 
         Ok(Self {
             socket,
             netcode_server,
             buffer: [0; NETCODE_MAX_PACKET_BYTES],
+            secure,
         })
     }
 
@@ -37,6 +44,17 @@ impl NetcodeServerTransport {
         self.netcode_server.addresses()
     }
 
+    /// Returns the maximum size in bytes of the renet packets the transport can send.
+    pub fn max_packet_size(&self) -> usize {
+        NETCODE_MAX_PAYLOAD_BYTES
+    }
+
+    /// Checks that the packets generated with the connection config fit in the transport,
+    /// see [`ConnectionConfig::validate_packet_size`].
+    pub fn validate_packet_size(&self, connection_config: &ConnectionConfig) -> Result<(), PacketSizeError> {
+        connection_config.validate_packet_size(self.max_packet_size())
+    }
+
     /// Returns the maximum number of clients that can be connected.
     pub fn max_clients(&self) -> usize {
         self.netcode_server.max_clients()
@@ -72,7 +90,7 @@ impl NetcodeServerTransport {
     pub fn disconnect_all(&mut self, server: &mut RenetServer) {
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.disconnect(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &self.socket, server, self.secure);
         }
     }
 
@@ -90,7 +108,7 @@ impl NetcodeServerTransport {
             match self.socket.recv_from(&mut self.buffer) {
                 Ok((len, addr)) => {
                     let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(server_result, &self.socket, server);
+                    handle_server_result(server_result, &self.socket, server, self.secure);
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
@@ -101,12 +119,12 @@ impl NetcodeServerTransport {
 
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.update_client(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &self.socket, server, self.secure);
         }
 
         for disconnection_id in server.disconnections_id() {
             let server_result = self.netcode_server.disconnect(disconnection_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &self.socket, server, self.secure);
         }
 
         Ok(())
@@ -134,7 +152,7 @@ impl NetcodeServerTransport {
     }
 }
 
-fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliable_server: &mut RenetServer) {
+fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliable_server: &mut RenetServer, secure: bool) {
     let send_packet = |packet: &[u8], addr: SocketAddr| {
         if let Err(err) = socket.send_to(packet, addr) {
             log::error!("Failed to send packet to {addr}: {err}");
@@ -146,11 +164,10 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
         ServerResult::PacketToSend { payload, addr } => {
             send_packet(payload, addr);
         }
-        ServerResult::Payload { client_id, payload } => {
-            if let Err(e) = reliable_server.process_packet_from(payload, client_id) {
-                log::error!("Error while processing payload for {}: {}", client_id, e);
-            }
-        }
+        ServerResult::Payload { client_id, payload } => match reliable_server.process_packet_from(payload, client_id) {
+            Ok(()) | Err(ProcessError::Duplicate | ProcessError::OutOfWindow) => {}
+            Err(e) => log::error!("Error while processing payload for {}: {}", client_id, e),
+        },
         ServerResult::ClientConnected {
             client_id,
             user_data: _,
@@ -158,6 +175,7 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
             payload,
         } => {
             reliable_server.add_connection(client_id);
+            reliable_server.set_secure(client_id, secure);
             send_packet(payload, addr);
         }
         ServerResult::ClientDisconnected { client_id, addr, payload } => {
@@ -0,0 +1,78 @@
+//! Compares the CPU time spent compressing the renet packets of a message with the bytes saved,
+//! run with `cargo bench -p renet_steam --bench compression`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use renet::{ConnectionConfig, DefaultChannel, RenetServer};
+use renet_steam::{compress_packet, decompress_packet};
+
+const ITERATIONS: u32 = 200;
+
+// Positions and rotations of entities that mostly stay in place, like an uncompressed world snapshot
+fn snapshot(entities: u32) -> Vec<u8> {
+    (0..entities)
+        .flat_map(|entity| {
+            let position = [entity as f32 * 0.5, 0.0, 10.0];
+            let rotation = [0.0f32, 0.0, 0.0, 1.0];
+            entity
+                .to_le_bytes()
+                .into_iter()
+                .chain(position.into_iter().chain(rotation).flat_map(f32::to_le_bytes))
+        })
+        .collect()
+}
+
+// Bytes without redundancy, like data the game already compressed
+fn noise(size: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn packets_for(payload: Vec<u8>) -> Vec<Vec<u8>> {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.add_connection(0);
+    server.send_message(0, DefaultChannel::Unreliable, payload);
+    server.get_packets_to_send(0).unwrap()
+}
+
+fn bench(name: &str, payload: Vec<u8>) {
+    let packets = packets_for(payload);
+    let packets_bytes: usize = packets.iter().map(Vec::len).sum();
+
+    let mut compressed = vec![];
+    let mut compress_time = Duration::ZERO;
+    let mut decompress_time = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        compressed = packets.iter().cloned().map(compress_packet).collect();
+        compress_time += start.elapsed();
+
+        let start = Instant::now();
+        for message in compressed.iter() {
+            black_box(decompress_packet(message));
+        }
+        decompress_time += start.elapsed();
+    }
+    let compressed_bytes: usize = compressed.iter().map(Vec::len).sum();
+
+    println!(
+        "{name}: {packets_bytes} -> {compressed_bytes} bytes ({:.0}%), compress {:?}, decompress {:?}",
+        compressed_bytes as f64 * 100.0 / packets_bytes as f64,
+        compress_time / ITERATIONS,
+        decompress_time / ITERATIONS,
+    );
+}
+
+fn main() {
+    bench("snapshot 100 entities", snapshot(100));
+    bench("snapshot 1000 entities", snapshot(1000));
+    bench("small input", vec![1, 0, 0, 0, 2, 0, 0, 0]);
+    bench("compressed data 20KB", noise(20 * 1024));
+}
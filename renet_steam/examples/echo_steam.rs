@@ -70,10 +70,7 @@ fn run_server(steam_client: Client<ClientManager>, single: SingleClient, with_lo
 
     let connection_config = ConnectionConfig::default();
     let mut server: RenetServer = RenetServer::new(connection_config);
-    let steam_transport_config = SteamServerConfig {
-        max_clients: 10,
-        access_permission,
-    };
+    let steam_transport_config = SteamServerConfig::new(10, access_permission);
     let mut transport = SteamServerTransport::new(&steam_client, steam_transport_config).unwrap();
 
     let mut received_messages = vec![];
@@ -1,7 +1,11 @@
 use std::net::{IpAddr, SocketAddr};
 
 use super::MAX_MESSAGE_BATCH_SIZE;
-use renet::RenetClient;
+use crate::{
+    message::{self, TransportMessage},
+    DisconnectCodes,
+};
+use renet::{ConnectionConfig, PacketSizeError, RenetClient};
 use steamworks::{
     networking_sockets::{InvalidHandle, NetConnection, NetworkingSockets},
     networking_types::{NetConnectionEnd, NetworkingConfigEntry, NetworkingConnectionState, NetworkingIdentity, SendFlags},
@@ -17,6 +21,15 @@ enum ConnectionState {
 pub struct SteamClientTransport {
     networking_sockets: NetworkingSockets<ClientManager>,
     state: ConnectionState,
+    app_disconnect_code: Option<i32>,
+    app_version: Option<u32>,
+    app_version_sent: bool,
+    spectator: bool,
+    connection_class_sent: bool,
+    session_redirect: Option<Vec<u8>>,
+    coalesce_packets: Option<usize>,
+    compress_packets: bool,
+    redirect_target: Option<SteamId>,
 }
 
 impl SteamClientTransport {
@@ -30,19 +43,40 @@ impl SteamClientTransport {
         Ok(Self {
             networking_sockets,
             state: ConnectionState::Connected { connection },
+            app_disconnect_code: None,
+            app_version: None,
+            app_version_sent: false,
+            spectator: false,
+            connection_class_sent: false,
+            session_redirect: None,
+            coalesce_packets: None,
+            compress_packets: false,
+            redirect_target: None,
         })
     }
 
-    pub fn new_ip(client: &steamworks::Client<ClientManager>, ip: IpAddr, port: u16, options: Vec<NetworkingConfigEntry>) -> Result<Self, InvalidHandle> {
-        let server_addr = SocketAddr::new(ip.into(), port);
+    pub fn new_ip(
+        client: &steamworks::Client<ClientManager>,
+        ip: IpAddr,
+        port: u16,
+        options: Vec<NetworkingConfigEntry>,
+    ) -> Result<Self, InvalidHandle> {
+        let server_addr = SocketAddr::new(ip, port);
 
         let networking_sockets = client.networking_sockets();
-        let connection = client
-            .networking_sockets()
-            .connect_by_ip_address(server_addr, options)?;
+        let connection = client.networking_sockets().connect_by_ip_address(server_addr, options)?;
         Ok(Self {
             networking_sockets,
             state: ConnectionState::Connected { connection },
+            app_disconnect_code: None,
+            app_version: None,
+            app_version_sent: false,
+            spectator: false,
+            connection_class_sent: false,
+            session_redirect: None,
+            coalesce_packets: None,
+            compress_packets: false,
+            redirect_target: None,
         })
     }
 
@@ -97,6 +131,70 @@ impl SteamClientTransport {
         None
     }
 
+    /// Returns the application disconnect code sent by the server,
+    /// see [`SteamServerTransport::disconnect_client_with_code`][crate::SteamServerTransport::disconnect_client_with_code].
+    pub fn app_disconnect_code(&self) -> Option<i32> {
+        self.app_disconnect_code
+    }
+
+    /// Returns the application disconnect reason sent by the server, mapped with the given codes.
+    pub fn app_disconnect_reason<'a, T: PartialEq>(&self, codes: &'a DisconnectCodes<T>) -> Option<&'a T> {
+        codes.reason(self.app_disconnect_code?)
+    }
+
+    /// Returns the redirect payload if the server disconnected the client because its session expired,
+    /// see [`SteamServerConfig::max_connection_lifetime`][crate::SteamServerConfig::max_connection_lifetime].
+    /// The payload is empty if the server did not set one.
+    pub fn session_expired(&self) -> Option<&[u8]> {
+        self.session_redirect.as_deref()
+    }
+
+    /// Returns the maximum size in bytes of the renet packets steam sends without fragmenting them,
+    /// or None if steam did not report its MTU.
+    pub fn max_packet_size(&self) -> Option<usize> {
+        crate::mtu_data_size()
+    }
+
+    /// Checks that the packets generated with the connection config are not fragmented by steam,
+    /// see [`ConnectionConfig::validate_packet_size`].
+    pub fn validate_packet_size(&self, connection_config: &ConnectionConfig) -> Result<(), PacketSizeError> {
+        crate::validate_packet_size(connection_config)
+    }
+
+    /// Returns the steam id of the host the server redirected the client to,
+    /// see [`SteamServerTransport::redirect_client`][crate::SteamServerTransport::redirect_client].
+    /// The reason is available with [`Self::app_disconnect_code`], the game should connect to the new host with a new transport.
+    pub fn redirect_target(&self) -> Option<SteamId> {
+        self.redirect_target
+    }
+
+    /// Connects as a spectator, counted against [`SteamServerConfig::max_spectators`][crate::SteamServerConfig::max_spectators]
+    /// instead of the player slots. Must be set before the first [`send_packets`](Self::send_packets) after connecting,
+    /// the class is sent once to the server before the renet packets. Clients that are not spectators send no class.
+    pub fn set_spectator(&mut self, spectator: bool) {
+        self.spectator = spectator;
+    }
+
+    /// Sets the application version sent to the server once connected,
+    /// see [`SteamServerTransport::set_required_app_version`][crate::SteamServerTransport::set_required_app_version].
+    pub fn set_app_version(&mut self, version: u32) {
+        self.app_version = Some(version);
+    }
+
+    /// Bundles the renet packets sent in a [`send_packets`](Self::send_packets) in steam messages of up to `max_size` bytes,
+    /// disabled when None, see [`SteamServerConfig::coalesce_packets`][crate::SteamServerConfig::coalesce_packets].
+    pub fn set_coalesce_packets(&mut self, max_size: Option<usize>) {
+        self.coalesce_packets = max_size;
+    }
+
+    /// Compresses the steam messages sent to the server, disabled by default,
+    /// see [`SteamServerConfig::compress_packets`][crate::SteamServerConfig::compress_packets].
+    pub fn set_compress_packets(&mut self, compress_packets: bool) {
+        self.compress_packets = compress_packets;
+    }
+
+    /// Returns the steam id of the user, used as client id by servers without a
+    /// [`client id generator`][crate::SteamServerTransport::set_client_id_generator].
     pub fn client_id(&self, steam_client: &steamworks::Client<ClientManager>) -> u64 {
         steam_client.user().steam_id().raw()
     }
@@ -145,9 +243,47 @@ impl SteamClientTransport {
         };
 
         if let Ok(messages) = connection.receive_messages(MAX_MESSAGE_BATCH_SIZE) {
-            messages.iter().for_each(|message| {
-                client.process_packet(message.data());
-            });
+            for message in messages.iter() {
+                let decompressed;
+                let data = match message::is_compressed(message.data()) {
+                    true => match message::decompress_packet(message.data()) {
+                        Some(data) => {
+                            decompressed = data;
+                            decompressed.as_slice()
+                        }
+                        None => {
+                            log::error!("Received invalid compressed message from the server");
+                            continue;
+                        }
+                    },
+                    false => message.data(),
+                };
+
+                if message::is_batch(data) {
+                    match message::split_batch(data) {
+                        Some(packets) => packets.into_iter().for_each(|packet| client.process_packet(packet)),
+                        None => log::error!("Received invalid coalesced packets from the server"),
+                    }
+                    continue;
+                }
+
+                if TransportMessage::is_transport_message(data) {
+                    match TransportMessage::from_bytes(data) {
+                        Some(TransportMessage::Disconnect { code }) => self.app_disconnect_code = Some(code),
+                        Some(TransportMessage::SessionExpired { redirect }) => self.session_redirect = Some(redirect),
+                        Some(TransportMessage::Redirect { target, code }) => {
+                            self.redirect_target = Some(SteamId::from_raw(target));
+                            self.app_disconnect_code = Some(code);
+                        }
+                        Some(TransportMessage::AppVersion { .. } | TransportMessage::ConnectionClass { .. }) | None => {
+                            log::error!("Received invalid transport message from the server")
+                        }
+                    }
+                    continue;
+                }
+
+                client.process_packet(data);
+            }
         }
     }
 
@@ -163,8 +299,24 @@ impl SteamClientTransport {
         let ConnectionState::Connected { connection } = &mut self.state else {
             unreachable!()
         };
-        let packets = client.get_packets_to_send();
+        if self.spectator && !self.connection_class_sent {
+            let message = TransportMessage::ConnectionClass { spectator: self.spectator };
+            connection.send_message(&message.to_bytes(), SendFlags::RELIABLE_NO_NAGLE)?;
+            self.connection_class_sent = true;
+        }
+        if let (Some(version), false) = (self.app_version, self.app_version_sent) {
+            let message = TransportMessage::AppVersion { version };
+            connection.send_message(&message.to_bytes(), SendFlags::RELIABLE_NO_NAGLE)?;
+            self.app_version_sent = true;
+        }
+
+        let mut packets = client.get_packets_to_send();
+        if let Some(max_size) = self.coalesce_packets {
+            packets = message::coalesce_packets(packets, max_size);
+        }
+
         for packet in packets {
+            let packet = if self.compress_packets { message::compress_packet(packet) } else { packet };
             connection.send_message(&packet, SendFlags::UNRELIABLE_NO_NAGLE)?;
         }
 
@@ -0,0 +1,67 @@
+use std::ops::RangeInclusive;
+
+/// Range of disconnect codes reserved by steam for the application.
+pub const APP_DISCONNECT_CODES: RangeInclusive<i32> = 1000..=1999;
+
+/// Table mapping application disconnect reasons to codes, shared between the server and the client.
+///
+/// The server disconnects with [`SteamServerTransport::disconnect_client_with_code`][crate::SteamServerTransport::disconnect_client_with_code],
+/// and the client maps the received code back with [`SteamClientTransport::app_disconnect_reason`][crate::SteamClientTransport::app_disconnect_reason].
+///
+/// # Usage
+/// ```
+/// # use renet_steam::DisconnectCodes;
+/// #[derive(Debug, PartialEq)]
+/// enum KickReason {
+///     Teamkilling,
+///     Idle,
+/// }
+///
+/// let mut codes = DisconnectCodes::new();
+/// codes.register(1001, KickReason::Teamkilling).register(1002, KickReason::Idle);
+///
+/// assert_eq!(codes.code(&KickReason::Teamkilling), Some(1001));
+/// assert_eq!(codes.reason(1002), Some(&KickReason::Idle));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DisconnectCodes<T> {
+    codes: Vec<(i32, T)>,
+}
+
+impl<T> Default for DisconnectCodes<T> {
+    fn default() -> Self {
+        Self { codes: Vec::new() }
+    }
+}
+
+impl<T: PartialEq> DisconnectCodes<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the code for the reason.
+    ///
+    /// # Panics
+    /// If the code is outside of [`APP_DISCONNECT_CODES`], or the code or reason is already registered.
+    pub fn register(&mut self, code: i32, reason: T) -> &mut Self {
+        assert!(
+            APP_DISCONNECT_CODES.contains(&code),
+            "disconnect code {code} is outside the application range"
+        );
+        assert!(self.reason(code).is_none(), "disconnect code {code} is already registered");
+        assert!(self.code(&reason).is_none(), "disconnect reason is already registered");
+
+        self.codes.push((code, reason));
+        self
+    }
+
+    /// Returns the code registered for the reason.
+    pub fn code(&self, reason: &T) -> Option<i32> {
+        self.codes.iter().find(|(_, r)| r == reason).map(|(code, _)| *code)
+    }
+
+    /// Returns the reason registered for the code.
+    pub fn reason(&self, code: i32) -> Option<&T> {
+        self.codes.iter().find(|(c, _)| *c == code).map(|(_, reason)| reason)
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    fmt,
+    sync::mpsc::{self, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use steamworks::{networking_sockets::InvalidHandle, Client, LobbyId, LobbyType, Manager, SingleClient, SteamError};
+
+use crate::{AccessPermission, SteamServerConfig, SteamServerTransport};
+
+/// Builder for the common host flow: creates a lobby, creates a server that only accepts its members
+/// and lets friends join from the steam friends list.
+///
+/// ```ignore
+/// let (mut transport, lobby_id) = SteamHostSession::new(config).max_members(8).host(&steam_client, &single)?;
+/// // ...
+/// transport.end_host_session(&mut server, lobby_id);
+/// ```
+///
+/// End the session with [`SteamServerTransport::end_host_session`], it disconnects the clients,
+/// leaves the lobby and clears the rich presence, and the listen socket is closed with the transport.
+pub struct SteamHostSession {
+    config: SteamServerConfig,
+    lobby_type: LobbyType,
+    max_members: u32,
+    rich_presence: bool,
+    timeout: Duration,
+}
+
+/// Possible errors when hosting a session, see [`SteamHostSession::host`].
+#[derive(Debug)]
+pub enum HostSessionError {
+    /// Steam failed to create the lobby
+    CreateLobby(SteamError),
+    /// Steam did not create the lobby before the timeout, see [`SteamHostSession::timeout`].
+    /// A lobby created after the timeout is not left, steam removes it once the host quits
+    Timeout,
+    /// Steam failed to create the listen socket, the lobby was left
+    ListenSocket(InvalidHandle),
+}
+
+impl std::error::Error for HostSessionError {}
+
+impl fmt::Display for HostSessionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use HostSessionError::*;
+
+        match self {
+            CreateLobby(err) => write!(fmt, "failed to create the lobby: {err}"),
+            Timeout => write!(fmt, "the lobby was not created before the timeout"),
+            ListenSocket(err) => write!(fmt, "failed to create the listen socket: {err}"),
+        }
+    }
+}
+
+impl SteamHostSession {
+    /// Hosts a public lobby with the members limit of the config, the access permission of the config is replaced
+    /// by [`AccessPermission::InLobby`].
+    pub fn new(config: SteamServerConfig) -> Self {
+        let max_members = config.max_clients.saturating_add(config.max_spectators).min(250) as u32;
+        Self {
+            config,
+            lobby_type: LobbyType::Public,
+            max_members,
+            rich_presence: true,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn lobby_type(mut self, lobby_type: LobbyType) -> Self {
+        self.lobby_type = lobby_type;
+        self
+    }
+
+    /// Maximum number of lobby members including the host, up to 250.
+    pub fn max_members(mut self, max_members: u32) -> Self {
+        self.max_members = max_members.min(250);
+        self
+    }
+
+    /// Whether the `connect` rich presence of the host is set to `+connect_lobby <lobby id>`, enabled by default.
+    /// Steam passes it to the game of friends that join from the friends list.
+    pub fn rich_presence(mut self, rich_presence: bool) -> Self {
+        self.rich_presence = rich_presence;
+        self
+    }
+
+    /// Maximum time to wait for steam to create the lobby, 10 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Creates the lobby and the server, running the steam callbacks until the lobby is created.
+    pub fn host<T: Manager + 'static>(
+        mut self,
+        client: &Client<T>,
+        single: &SingleClient<T>,
+    ) -> Result<(SteamServerTransport<T>, LobbyId), HostSessionError> {
+        let (sender, receiver) = mpsc::channel();
+        client.matchmaking().create_lobby(self.lobby_type, self.max_members, move |lobby| {
+            let _ = sender.send(lobby);
+        });
+
+        let started_at = Instant::now();
+        let lobby_id = loop {
+            single.run_callbacks();
+            match receiver.try_recv() {
+                Ok(lobby) => break lobby.map_err(HostSessionError::CreateLobby)?,
+                Err(TryRecvError::Disconnected) => return Err(HostSessionError::Timeout),
+                Err(TryRecvError::Empty) if started_at.elapsed() >= self.timeout => return Err(HostSessionError::Timeout),
+                Err(TryRecvError::Empty) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        self.config.access_permission = AccessPermission::InLobby(lobby_id);
+        let transport = match SteamServerTransport::new(client, self.config) {
+            Ok(transport) => transport,
+            Err(err) => {
+                client.matchmaking().leave_lobby(lobby_id);
+                return Err(HostSessionError::ListenSocket(err));
+            }
+        };
+
+        if self.rich_presence {
+            let connect = format!("+connect_lobby {}", lobby_id.raw());
+            if !client.friends().set_rich_presence("connect", Some(&connect)) {
+                log::warn!("Failed to set the connect rich presence for lobby {}", lobby_id.raw());
+            }
+        }
+
+        Ok((transport, lobby_id))
+    }
+}
@@ -1,11 +1,81 @@
 const MAX_MESSAGE_BATCH_SIZE: usize = 512;
-const MAX_MESSAGE_BUFFER_SIZE: usize = 500 * 1024;
 
 mod client;
+mod disconnect;
+mod host;
+mod log_limiter;
+mod message;
+mod mock;
+mod recording;
 mod server;
+mod throughput;
 
 pub use client::SteamClientTransport;
-pub use server::{AccessPermission, SteamServerConfig, SteamServerTransport};
+pub use disconnect::{DisconnectCodes, APP_DISCONNECT_CODES};
+pub use host::{HostSessionError, SteamHostSession};
+pub use mock::{assert_ordered_delivery, check_ordered_delivery, DeliveryError, MockSteamTransport, NetworkSimulator};
+pub use recording::{InvalidRecording, RecordedEvent, RecordedEventKind, SessionRecording};
+pub use server::{
+    AccessLists, AccessPermission, AuthSessionResponse, DegradedAccessPolicy, LaneStats, ReceivedMessage, RejectionReason, SendOrder,
+    SteamConnectionStats, SteamServerConfig, SteamServerEvent, SteamServerStats, SteamServerTransport, SteamStatus, TimeoutMismatch,
+    UnknownClientPolicy,
+};
+pub use throughput::{Throughput, ThroughputDropConfig};
 
 #[doc(hidden)]
 pub use steamworks;
+
+// Used by the compression benchmark
+#[doc(hidden)]
+pub use message::{compress_packet, decompress_packet};
+
+// Maximum message size that steam sends without fragmenting, based on the global MTU config
+fn mtu_data_size() -> Option<usize> {
+    let value = global_config_int32(steamworks::sys::ESteamNetworkingConfigValue::k_ESteamNetworkingConfig_MTU_DataSize)?;
+    usize::try_from(value).ok()
+}
+
+// Time steam waits for a connected peer before closing the connection, based on the global config
+fn connection_timeout() -> Option<std::time::Duration> {
+    let value = global_config_int32(steamworks::sys::ESteamNetworkingConfigValue::k_ESteamNetworkingConfig_TimeoutConnected)?;
+    Some(std::time::Duration::from_millis(u64::try_from(value).ok()?))
+}
+
+fn global_config_int32(config_value: steamworks::sys::ESteamNetworkingConfigValue) -> Option<i32> {
+    use steamworks::sys::{ESteamNetworkingConfigDataType, ESteamNetworkingConfigScope, ESteamNetworkingGetConfigValueResult};
+
+    let mut data_type = ESteamNetworkingConfigDataType::k_ESteamNetworkingConfig_Int32;
+    let mut value: i32 = 0;
+    let mut size = std::mem::size_of::<i32>();
+    // SAFETY: the networking utils interface is valid while steam is initialized, the transports require a steam client.
+    // The result buffer is an i32 as expected by the int32 config values it is called with.
+    let result = unsafe {
+        let utils = steamworks::sys::SteamAPI_SteamNetworkingUtils_SteamAPI_v004();
+        steamworks::sys::SteamAPI_ISteamNetworkingUtils_GetConfigValue(
+            utils,
+            config_value,
+            ESteamNetworkingConfigScope::k_ESteamNetworkingConfig_Global,
+            0,
+            &mut data_type,
+            &mut value as *mut i32 as *mut std::ffi::c_void,
+            &mut size,
+        )
+    };
+
+    match result {
+        ESteamNetworkingGetConfigValueResult::k_ESteamNetworkingGetConfigValue_OK
+        | ESteamNetworkingGetConfigValueResult::k_ESteamNetworkingGetConfigValue_OKInherited => Some(value),
+        _ => None,
+    }
+}
+
+// Checks the renet packets against the steam MTU, nothing is checked if steam did not report it
+fn validate_packet_size(connection_config: &renet::ConnectionConfig) -> Result<(), renet::PacketSizeError> {
+    match mtu_data_size() {
+        Some(mtu_data_size) => connection_config.validate_packet_size(mtu_data_size),
+        None => {
+            log::warn!("Could not read the steam MTU, the renet packet size was not validated");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use renet::ClientId;
+
+// Number of repeated errors from the same client between each logged one
+const LOG_EVERY_OCCURRENCES: u64 = 100;
+
+// Limits repeated errors from misbehaving clients flooding the logs,
+// the first occurrence is always logged, then once every LOG_EVERY_OCCURRENCES.
+#[derive(Debug, Default)]
+pub(crate) struct LogLimiter {
+    occurrences: HashMap<ClientId, u64>,
+}
+
+impl LogLimiter {
+    // Registers an occurrence for the client,
+    // returns the total of occurrences if this one should be logged.
+    pub fn occurrence(&mut self, client_id: ClientId) -> Option<u64> {
+        let occurrences = self.occurrences.entry(client_id).or_insert(0);
+        *occurrences += 1;
+
+        if *occurrences % LOG_EVERY_OCCURRENCES == 1 {
+            Some(*occurrences)
+        } else {
+            None
+        }
+    }
+
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.occurrences.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_once_every_occurrences() {
+        let mut limiter = LogLimiter::default();
+
+        assert_eq!(limiter.occurrence(0), Some(1));
+        for _ in 1..LOG_EVERY_OCCURRENCES {
+            assert_eq!(limiter.occurrence(0), None);
+        }
+        assert_eq!(limiter.occurrence(0), Some(LOG_EVERY_OCCURRENCES + 1));
+
+        // Each client has its own count
+        assert_eq!(limiter.occurrence(1), Some(1));
+
+        limiter.remove_client(0);
+        assert_eq!(limiter.occurrence(0), Some(1));
+    }
+}
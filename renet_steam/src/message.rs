@@ -0,0 +1,339 @@
+// Messages exchanged only between the steam transports, they are never passed to renet.
+// Renet packets always start with the packet type (0 to 7), so a marker byte tells them apart.
+const TRANSPORT_MESSAGE_MARKER: u8 = 0xFF;
+// Transport message type of coalesced renet packets, kept out of TransportMessage
+// so the received packets can be split without copying them.
+const BATCH_MESSAGE_TYPE: u8 = 3;
+// Transport message type of messages compressed with deflate, the decompressed data is a renet packet or a batch
+const COMPRESSED_MESSAGE_TYPE: u8 = 5;
+// Fastest deflate level, the compression runs for every packet sent
+const COMPRESSION_LEVEL: u8 = 1;
+// Largest message steam sends, bigger decompressed data is discarded
+const MAX_DECOMPRESSED_SIZE: usize = 512 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TransportMessage {
+    // Sent by the server right before closing the connection
+    Disconnect { code: i32 },
+    // Sent by the client right after connecting
+    AppVersion { version: u32 },
+    // Sent by the server right before closing a connection that reached its maximum lifetime
+    SessionExpired { redirect: Vec<u8> },
+    // Sent by the server right before closing a connection handed off to another host
+    Redirect { target: u64, code: i32 },
+    // Sent by the client right after connecting, before its version
+    ConnectionClass { spectator: bool },
+}
+
+impl TransportMessage {
+    pub fn is_transport_message(payload: &[u8]) -> bool {
+        payload.first() == Some(&TRANSPORT_MESSAGE_MARKER)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![TRANSPORT_MESSAGE_MARKER];
+        match self {
+            TransportMessage::Disconnect { code } => {
+                bytes.push(0);
+                bytes.extend_from_slice(&code.to_le_bytes());
+            }
+            TransportMessage::AppVersion { version } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&version.to_le_bytes());
+            }
+            TransportMessage::SessionExpired { redirect } => {
+                bytes.push(2);
+                bytes.extend_from_slice(redirect);
+            }
+            TransportMessage::Redirect { target, code } => {
+                bytes.push(4);
+                bytes.extend_from_slice(&target.to_le_bytes());
+                bytes.extend_from_slice(&code.to_le_bytes());
+            }
+            TransportMessage::ConnectionClass { spectator } => {
+                bytes.push(6);
+                bytes.push(*spectator as u8);
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(payload: &[u8]) -> Option<Self> {
+        let [TRANSPORT_MESSAGE_MARKER, message_type, data @ ..] = payload else {
+            return None;
+        };
+
+        match message_type {
+            0 => {
+                let code = i32::from_le_bytes(data.try_into().ok()?);
+                Some(TransportMessage::Disconnect { code })
+            }
+            1 => {
+                let version = u32::from_le_bytes(data.try_into().ok()?);
+                Some(TransportMessage::AppVersion { version })
+            }
+            2 => Some(TransportMessage::SessionExpired { redirect: data.to_vec() }),
+            4 => {
+                let data: [u8; 12] = data.try_into().ok()?;
+                let target = u64::from_le_bytes(data[..8].try_into().unwrap());
+                let code = i32::from_le_bytes(data[8..].try_into().unwrap());
+                Some(TransportMessage::Redirect { target, code })
+            }
+            6 => match data {
+                [0] => Some(TransportMessage::ConnectionClass { spectator: false }),
+                [1] => Some(TransportMessage::ConnectionClass { spectator: true }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// Bundles renet packets in messages of up to max_size bytes, each packet is prefixed with its length as an u16.
+// Steam messages are delivered whole or not at all, so a batch is never partially read,
+// but the framing is still checked when splitting. Packets that do not fit with others,
+// like packets bigger than max_size, are sent alone without the framing.
+pub(crate) fn coalesce_packets(packets: Vec<Vec<u8>>, max_size: usize) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut batch: Vec<Vec<u8>> = Vec::new();
+    let mut batch_size = 2;
+    for packet in packets {
+        let packet_size = packet.len() + 2;
+        if batch_size + packet_size > max_size && !batch.is_empty() {
+            messages.push(finish_batch(std::mem::take(&mut batch)));
+            batch_size = 2;
+        }
+
+        if batch_size + packet_size > max_size || packet.len() > u16::MAX as usize {
+            // Fallback for packets that do not fit in a batch
+            messages.push(packet);
+            continue;
+        }
+
+        batch_size += packet_size;
+        batch.push(packet);
+    }
+
+    if !batch.is_empty() {
+        messages.push(finish_batch(batch));
+    }
+
+    messages
+}
+
+fn finish_batch(mut batch: Vec<Vec<u8>>) -> Vec<u8> {
+    if batch.len() == 1 {
+        return batch.pop().unwrap();
+    }
+
+    let size = batch.iter().map(|packet| packet.len() + 2).sum::<usize>();
+    let mut bytes = Vec::with_capacity(size + 2);
+    bytes.push(TRANSPORT_MESSAGE_MARKER);
+    bytes.push(BATCH_MESSAGE_TYPE);
+    for packet in batch {
+        bytes.extend_from_slice(&(packet.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&packet);
+    }
+
+    bytes
+}
+
+pub(crate) fn is_batch(payload: &[u8]) -> bool {
+    matches!(payload, [TRANSPORT_MESSAGE_MARKER, BATCH_MESSAGE_TYPE, ..])
+}
+
+// Returns the packets of a batch, or None if the batch is truncated or malformed
+pub(crate) fn split_batch(payload: &[u8]) -> Option<Vec<&[u8]>> {
+    let [TRANSPORT_MESSAGE_MARKER, BATCH_MESSAGE_TYPE, data @ ..] = payload else {
+        return None;
+    };
+    if data.is_empty() {
+        return None;
+    }
+
+    let mut data = data;
+
+    let mut packets = Vec::new();
+    while !data.is_empty() {
+        let [len_0, len_1, rest @ ..] = data else {
+            return None;
+        };
+        let len = u16::from_le_bytes([*len_0, *len_1]) as usize;
+        if rest.len() < len {
+            return None;
+        }
+
+        let (packet, rest) = rest.split_at(len);
+        packets.push(packet);
+        data = rest;
+    }
+
+    Some(packets)
+}
+
+// Compresses the whole message, returns it unchanged when the compression does not make it smaller,
+// like for already compressed or small packets.
+pub fn compress_packet(packet: Vec<u8>) -> Vec<u8> {
+    let compressed = miniz_oxide::deflate::compress_to_vec(&packet, COMPRESSION_LEVEL);
+    if compressed.len() + 2 >= packet.len() {
+        return packet;
+    }
+
+    let mut bytes = Vec::with_capacity(compressed.len() + 2);
+    bytes.push(TRANSPORT_MESSAGE_MARKER);
+    bytes.push(COMPRESSED_MESSAGE_TYPE);
+    bytes.extend_from_slice(&compressed);
+    bytes
+}
+
+pub(crate) fn is_compressed(payload: &[u8]) -> bool {
+    matches!(payload, [TRANSPORT_MESSAGE_MARKER, COMPRESSED_MESSAGE_TYPE, ..])
+}
+
+// Returns the message compressed by compress_packet, or None if the data is invalid or too big
+pub fn decompress_packet(payload: &[u8]) -> Option<Vec<u8>> {
+    let [TRANSPORT_MESSAGE_MARKER, COMPRESSED_MESSAGE_TYPE, data @ ..] = payload else {
+        return None;
+    };
+
+    miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_DECOMPRESSED_SIZE).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_and_split_packets() {
+        let packets = vec![vec![0; 100], vec![1; 200], vec![2; 300], vec![4; 10]];
+        let messages = coalesce_packets(packets.clone(), 400);
+        assert_eq!(messages.len(), 2);
+
+        let received: Vec<Vec<u8>> = messages
+            .iter()
+            .flat_map(|message| match is_batch(message) {
+                true => split_batch(message).unwrap(),
+                false => vec![message.as_slice()],
+            })
+            .map(<[u8]>::to_vec)
+            .collect();
+        assert_eq!(received, packets);
+    }
+
+    #[test]
+    fn coalesce_fallback() {
+        // A single packet and packets bigger than the max size are sent without framing
+        let messages = coalesce_packets(vec![vec![0; 10]], 1200);
+        assert_eq!(messages, vec![vec![0; 10]]);
+
+        let messages = coalesce_packets(vec![vec![0; 10], vec![2; 2000], vec![1; 10]], 1200);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1], vec![2; 2000]);
+        assert!(!is_batch(&messages[0]));
+        assert!(!is_batch(&messages[2]));
+    }
+
+    #[test]
+    fn split_invalid_batch() {
+        let batch = coalesce_packets(vec![vec![0; 10], vec![1; 10]], 1200).pop().unwrap();
+        assert!(is_batch(&batch));
+        assert!(TransportMessage::is_transport_message(&batch));
+        assert_eq!(TransportMessage::from_bytes(&batch), None);
+
+        for len in 2..batch.len() {
+            if len == 14 {
+                // Ends right after the first packet
+                assert_eq!(split_batch(&batch[..len]), Some(vec![&[0; 10][..]]));
+                continue;
+            }
+            assert_eq!(split_batch(&batch[..len]), None, "truncated at {len}");
+        }
+    }
+
+    #[test]
+    fn serialize_disconnect() {
+        let message = TransportMessage::Disconnect { code: 1001 };
+        let bytes = message.to_bytes();
+
+        assert!(TransportMessage::is_transport_message(&bytes));
+        assert_eq!(TransportMessage::from_bytes(&bytes), Some(message));
+        assert_eq!(TransportMessage::from_bytes(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn serialize_app_version() {
+        let message = TransportMessage::AppVersion { version: 42 };
+        let bytes = message.to_bytes();
+
+        assert!(TransportMessage::is_transport_message(&bytes));
+        assert_eq!(TransportMessage::from_bytes(&bytes), Some(message));
+    }
+
+    #[test]
+    fn serialize_session_expired() {
+        let message = TransportMessage::SessionExpired {
+            redirect: b"eu-west-2".to_vec(),
+        };
+        let bytes = message.to_bytes();
+
+        assert!(TransportMessage::is_transport_message(&bytes));
+        assert_eq!(TransportMessage::from_bytes(&bytes), Some(message));
+    }
+
+    #[test]
+    fn compress_and_decompress_packets() {
+        let snapshot: Vec<u8> = (0..1000u32).flat_map(|i| (i % 16).to_le_bytes()).collect();
+        let compressed = compress_packet(snapshot.clone());
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < snapshot.len());
+        assert_eq!(decompress_packet(&compressed), Some(snapshot));
+
+        // Batches are compressed whole
+        let batch = coalesce_packets(vec![vec![1; 300], vec![2; 300]], 1200).pop().unwrap();
+        let compressed = compress_packet(batch.clone());
+        assert_eq!(decompress_packet(&compressed), Some(batch.clone()));
+        assert_eq!(decompress_packet(&batch), None);
+
+        // Incompressible packets are sent as they are
+        let small = vec![0, 1, 2];
+        assert_eq!(compress_packet(small.clone()), small);
+        assert!(!is_compressed(&small));
+
+        assert_eq!(
+            decompress_packet(&[TRANSPORT_MESSAGE_MARKER, COMPRESSED_MESSAGE_TYPE, 7, 7, 7]),
+            None
+        );
+    }
+
+    #[test]
+    fn serialize_connection_class() {
+        for spectator in [false, true] {
+            let message = TransportMessage::ConnectionClass { spectator };
+            assert_eq!(TransportMessage::from_bytes(&message.to_bytes()), Some(message));
+        }
+        assert_eq!(TransportMessage::from_bytes(&[TRANSPORT_MESSAGE_MARKER, 6, 2]), None);
+        assert_eq!(TransportMessage::from_bytes(&[TRANSPORT_MESSAGE_MARKER, 6]), None);
+    }
+
+    #[test]
+    fn serialize_redirect() {
+        let message = TransportMessage::Redirect {
+            target: 76561197960287930,
+            code: 1002,
+        };
+        let bytes = message.to_bytes();
+
+        assert!(TransportMessage::is_transport_message(&bytes));
+        assert!(!is_batch(&bytes));
+        assert_eq!(TransportMessage::from_bytes(&bytes), Some(message));
+        assert_eq!(TransportMessage::from_bytes(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn renet_packets_are_not_transport_messages() {
+        for packet_type in 0..=7 {
+            assert!(!TransportMessage::is_transport_message(&[packet_type, 0, 0]));
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    time::Duration,
+};
+
+use renet::{ClientId, ConnectionConfig, DisconnectReason, ProcessError, RenetClient, RenetServer};
+
+use crate::{
+    recording::{RecordedEvent, RecordedEventKind, SessionRecording},
+    UnknownClientPolicy,
+};
+
+/// Network conditions simulated for the packets of a mock connection.
+///
+/// The conditions are applied in both directions, the same seed always produces the same results.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkSimulator {
+    /// Delay added to every packet.
+    pub latency: Duration,
+    /// Maximum random delay added on top of the latency, packets can arrive out of order when set.
+    pub jitter: Duration,
+    /// Chance of a packet being lost, from 0.0 to 1.0.
+    pub packet_loss: f64,
+    /// Seed used for the random jitter and loss.
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+struct InFlightPacket {
+    deliver_at: Duration,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct MockConnection {
+    client: RenetClient,
+    conditions: NetworkSimulator,
+    rng_state: u64,
+    to_server: Vec<InFlightPacket>,
+    to_client: Vec<InFlightPacket>,
+}
+
+/// In-memory transport that mimics [`SteamServerTransport`][crate::SteamServerTransport] without steamworks.
+///
+/// The transport owns a [`RenetClient`] for each connection, so integration tests can exercise
+/// the server and its clients in the same process with deterministic network conditions.
+/// Clients are updated in ascending client id order, so runs with the same seed process the same packet sequence.
+///
+/// A [`SessionRecording`] can also be [`replayed`](Self::replay) to reproduce the exact packet sequence
+/// a server received, the replayed clients have no [`RenetClient`].
+#[derive(Debug)]
+pub struct MockSteamTransport {
+    connection_config: ConnectionConfig,
+    connections: BTreeMap<ClientId, MockConnection>,
+    current_time: Duration,
+    recording: Option<(Duration, SessionRecording)>,
+    replay: VecDeque<RecordedEvent>,
+    replay_started_at: Duration,
+    replayed_clients: BTreeSet<ClientId>,
+    unknown_client_policy: UnknownClientPolicy,
+    unknown_clients: BTreeSet<ClientId>,
+}
+
+impl NetworkSimulator {
+    // SplitMix64, good enough for simulated conditions and has no dependencies
+    fn next_random(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_unit(state: &mut u64) -> f64 {
+        (Self::next_random(state) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns the delay for a packet, or None if the packet was lost.
+    fn simulate(&self, state: &mut u64) -> Option<Duration> {
+        if self.packet_loss > 0.0 && Self::next_unit(state) < self.packet_loss {
+            return None;
+        }
+
+        let jitter = self.jitter.mul_f64(Self::next_unit(state));
+        Some(self.latency + jitter)
+    }
+}
+
+impl MockConnection {
+    fn new(client: RenetClient) -> Self {
+        Self {
+            client,
+            conditions: NetworkSimulator::default(),
+            rng_state: 0,
+            to_server: Vec::new(),
+            to_client: Vec::new(),
+        }
+    }
+}
+
+fn take_due_packets(packets: &mut Vec<InFlightPacket>, current_time: Duration) -> Vec<InFlightPacket> {
+    // Stable sort, packets with the same delay keep the send order
+    packets.sort_by_key(|packet| packet.deliver_at);
+    let due = packets.iter().take_while(|packet| packet.deliver_at <= current_time).count();
+    packets.drain(..due).collect()
+}
+
+impl MockSteamTransport {
+    pub fn new(connection_config: ConnectionConfig) -> Self {
+        Self {
+            connection_config,
+            connections: BTreeMap::new(),
+            current_time: Duration::ZERO,
+            recording: None,
+            replay: VecDeque::new(),
+            replay_started_at: Duration::ZERO,
+            replayed_clients: BTreeSet::new(),
+            unknown_client_policy: UnknownClientPolicy::default(),
+            unknown_clients: BTreeSet::new(),
+        }
+    }
+
+    /// Sets what [`send_packets`](Self::send_packets) does with the clients added to the server without the transport,
+    /// like [`SteamServerConfig::unknown_client_policy`][crate::SteamServerConfig::unknown_client_policy].
+    pub fn set_unknown_client_policy(&mut self, policy: UnknownClientPolicy) {
+        self.unknown_client_policy = policy;
+    }
+
+    /// Returns the clients reported with [`UnknownClientPolicy::Event`] that are still connected to the server.
+    pub fn unknown_clients(&self) -> Vec<ClientId> {
+        self.unknown_clients.iter().copied().collect()
+    }
+
+    /// Starts recording the clients connected and the packets delivered to the server, in simulated time,
+    /// like [`SteamServerTransport::start_recording`][crate::SteamServerTransport::start_recording].
+    pub fn start_recording(&mut self) {
+        self.recording = Some((self.current_time, SessionRecording::default()));
+    }
+
+    /// Stops the recording and returns it, or None if [`start_recording`](Self::start_recording) was not called.
+    pub fn take_recording(&mut self) -> Option<SessionRecording> {
+        self.recording.take().map(|(_, recording)| recording)
+    }
+
+    fn record(&mut self, client_id: ClientId, kind: RecordedEventKind) {
+        if let Some((started_at, recording)) = &mut self.recording {
+            recording.push(self.current_time - *started_at, client_id, kind);
+        }
+    }
+
+    /// Replays the recorded session from now, replacing the replay in progress.
+    ///
+    /// Each [`update`](Self::update) applies to the server the recorded events that are due,
+    /// in the recorded order: connecting the clients, processing their packets and removing them.
+    /// The packets the server sends to the replayed clients are discarded in [`send_packets`](Self::send_packets).
+    /// Update the server and the transport with the same durations as the recorded session
+    /// for the server to generate the same packets, usually the frame time of the game.
+    pub fn replay(&mut self, recording: SessionRecording) {
+        self.replay = recording.events.into();
+        self.replay_started_at = self.current_time;
+    }
+
+    /// Returns true when all the events of the [`replay`](Self::replay) were applied.
+    pub fn is_replay_finished(&self) -> bool {
+        self.replay.is_empty()
+    }
+
+    fn apply_replay(&mut self, server: &mut RenetServer) {
+        let elapsed = self.current_time - self.replay_started_at;
+        while self.replay.front().is_some_and(|event| event.at <= elapsed) {
+            let event = self.replay.pop_front().unwrap();
+            let client_id = event.client_id;
+            match event.kind {
+                RecordedEventKind::Connected => {
+                    server.add_connection(client_id);
+                    self.replayed_clients.insert(client_id);
+                }
+                RecordedEventKind::Packet(packet) => match server.process_packet_from(&packet, client_id) {
+                    Ok(()) | Err(ProcessError::Duplicate | ProcessError::OutOfWindow) => {}
+                    Err(e) => log::error!("Error while replaying payload for {}: {}", client_id, e),
+                },
+                RecordedEventKind::Disconnected => {
+                    server.remove_connection(client_id);
+                    self.replayed_clients.remove(&client_id);
+                }
+            }
+        }
+    }
+
+    /// Creates a new connected client and adds it to the server.
+    /// If the client already exists it does nothing.
+    pub fn connect(&mut self, client_id: ClientId, server: &mut RenetServer) {
+        if self.connections.contains_key(&client_id) {
+            return;
+        }
+
+        let mut client = RenetClient::new(self.connection_config.clone());
+        client.set_connected();
+        server.add_connection(client_id);
+        self.connections.insert(client_id, MockConnection::new(client));
+        self.record(client_id, RecordedEventKind::Connected);
+    }
+
+    /// Disconnects a client from the server.
+    pub fn disconnect_client(&mut self, client_id: ClientId, server: &mut RenetServer) {
+        if let Some(mut connection) = self.connections.remove(&client_id) {
+            connection.client.disconnect_due_to_transport();
+            self.record(client_id, RecordedEventKind::Disconnected);
+        }
+        server.remove_connection(client_id);
+    }
+
+    /// Sets the simulated network conditions for the client, packets already in flight are not affected.
+    pub fn set_conditions(&mut self, client_id: ClientId, conditions: NetworkSimulator) {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            connection.rng_state = conditions.seed;
+            connection.conditions = conditions;
+        }
+    }
+
+    /// Returns the client for the connection.
+    pub fn client(&self, client_id: ClientId) -> Option<&RenetClient> {
+        self.connections.get(&client_id).map(|connection| &connection.client)
+    }
+
+    /// Returns the client for the connection as mutable.
+    pub fn client_mut(&mut self, client_id: ClientId) -> Option<&mut RenetClient> {
+        self.connections.get_mut(&client_id).map(|connection| &mut connection.client)
+    }
+
+    /// Return ids for all mock connections.
+    pub fn clients_id(&self) -> Vec<ClientId> {
+        self.connections.keys().copied().collect()
+    }
+
+    /// Advances the transport and the mock clients by the duration,
+    /// and delivers packets that finished crossing the simulated network.
+    pub fn update(&mut self, duration: Duration, server: &mut RenetServer) {
+        self.current_time += duration;
+        self.apply_replay(server);
+
+        let recording_time = self.recording.as_ref().map(|(started_at, _)| self.current_time - *started_at);
+        for (&client_id, connection) in self.connections.iter_mut() {
+            connection.client.update(duration);
+
+            for packet in take_due_packets(&mut connection.to_client, self.current_time) {
+                connection.client.process_packet(&packet.payload);
+            }
+
+            for packet in take_due_packets(&mut connection.to_server, self.current_time) {
+                if let (Some((_, recording)), Some(at)) = (&mut self.recording, recording_time) {
+                    recording.push(at, client_id, RecordedEventKind::Packet(packet.payload.clone()));
+                }
+
+                match server.process_packet_from(&packet.payload, client_id) {
+                    Ok(()) | Err(ProcessError::Duplicate | ProcessError::OutOfWindow) => {}
+                    Err(e) => log::error!("Error while processing payload for {}: {}", client_id, e),
+                }
+            }
+        }
+
+        let disconnected: Vec<ClientId> = self
+            .connections
+            .iter()
+            .filter(|(&client_id, connection)| connection.client.is_disconnected() || server.disconnect_reason(client_id).is_some())
+            .map(|(&client_id, _)| client_id)
+            .collect();
+        for client_id in disconnected {
+            self.disconnect_client(client_id, server);
+        }
+    }
+
+    /// Sends packets from the server to the mock clients and from the mock clients to the server.
+    pub fn send_packets(&mut self, server: &mut RenetServer) {
+        self.unknown_clients.retain(|&client_id| server.is_connected(client_id));
+        for client_id in server.clients_id() {
+            if self.connections.contains_key(&client_id) || self.replayed_clients.contains(&client_id) {
+                continue;
+            }
+            match self.unknown_client_policy {
+                UnknownClientPolicy::Log => log::error!("Error while sending packet to client {client_id}: connection not found"),
+                UnknownClientPolicy::Remove => server.remove_connection(client_id),
+                UnknownClientPolicy::Event => {
+                    self.unknown_clients.insert(client_id);
+                }
+            }
+        }
+
+        for &client_id in self.replayed_clients.iter() {
+            if server.get_packets_to_send(client_id).is_err() {
+                log::error!("Error while sending packet: connection not found");
+            }
+        }
+
+        for (&client_id, connection) in self.connections.iter_mut() {
+            let Ok(server_packets) = server.get_packets_to_send(client_id) else {
+                log::error!("Error while sending packet: connection not found");
+                continue;
+            };
+
+            for payload in server_packets {
+                if let Some(delay) = connection.conditions.simulate(&mut connection.rng_state) {
+                    connection.to_client.push(InFlightPacket {
+                        deliver_at: self.current_time + delay,
+                        payload,
+                    });
+                }
+            }
+
+            for payload in connection.client.get_packets_to_send() {
+                if let Some(delay) = connection.conditions.simulate(&mut connection.rng_state) {
+                    connection.to_server.push(InFlightPacket {
+                        deliver_at: self.current_time + delay,
+                        payload,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Simulated time step used when checking the delivery
+const DELIVERY_TICK: Duration = Duration::from_millis(10);
+// Maximum simulated time to deliver all messages
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Error returned by [`check_ordered_delivery`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    /// A message was received before the previous ones
+    OutOfOrder { expected: u64, received: u64 },
+    /// Not all messages were received in the simulated time
+    Incomplete { received: u64 },
+    /// The client disconnected before receiving all messages
+    Disconnected { reason: Option<DisconnectReason> },
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::OutOfOrder { expected, received } => write!(fmt, "expected message {expected} but received {received}"),
+            DeliveryError::Incomplete { received } => write!(fmt, "only {received} messages were received"),
+            DeliveryError::Disconnected { reason: Some(reason) } => write!(fmt, "client disconnected: {reason}"),
+            DeliveryError::Disconnected { reason: None } => write!(fmt, "client disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+/// Sends messages from a server to a mock client over the channel with the network conditions,
+/// and checks the client receives all of them in the order they were sent.
+///
+/// Use it to validate that a channel configuration keeps its guarantees under reordering and loss.
+///
+/// # Usage
+/// ```
+/// # use std::time::Duration;
+/// # use renet::{ConnectionConfig, DefaultChannel};
+/// # use renet_steam::{check_ordered_delivery, NetworkSimulator};
+/// let conditions = NetworkSimulator {
+///     latency: Duration::from_millis(50),
+///     jitter: Duration::from_millis(30),
+///     packet_loss: 0.2,
+///     seed: 7,
+/// };
+/// check_ordered_delivery(ConnectionConfig::default(), conditions, DefaultChannel::ReliableOrdered.into(), 100).unwrap();
+/// ```
+pub fn check_ordered_delivery(
+    connection_config: ConnectionConfig,
+    conditions: NetworkSimulator,
+    channel_id: u8,
+    message_count: u64,
+) -> Result<(), DeliveryError> {
+    const CLIENT_ID: ClientId = 0;
+
+    let mut server = RenetServer::new(connection_config.clone());
+    let mut transport = MockSteamTransport::new(connection_config);
+    transport.connect(CLIENT_ID, &mut server);
+    transport.set_conditions(CLIENT_ID, conditions);
+
+    for i in 0..message_count {
+        server.send_message(CLIENT_ID, channel_id, i.to_le_bytes().to_vec());
+    }
+
+    let mut expected = 0;
+    let mut elapsed = Duration::ZERO;
+    while expected < message_count {
+        if elapsed >= DELIVERY_TIMEOUT {
+            return Err(DeliveryError::Incomplete { received: expected });
+        }
+
+        server.update(DELIVERY_TICK);
+        transport.update(DELIVERY_TICK, &mut server);
+        let Some(client) = transport.client_mut(CLIENT_ID) else {
+            let reason = server.disconnect_reason(CLIENT_ID);
+            return Err(DeliveryError::Disconnected { reason });
+        };
+
+        while let Some(message) = client.receive_message(channel_id) {
+            let received = message.as_ref().try_into().map(u64::from_le_bytes).unwrap_or(u64::MAX);
+            if received != expected {
+                return Err(DeliveryError::OutOfOrder { expected, received });
+            }
+            expected += 1;
+        }
+
+        transport.send_packets(&mut server);
+        elapsed += DELIVERY_TICK;
+    }
+
+    Ok(())
+}
+
+/// Same as [`check_ordered_delivery`], but panics if the messages were not delivered in order.
+pub fn assert_ordered_delivery(connection_config: ConnectionConfig, conditions: NetworkSimulator, channel_id: u8, message_count: u64) {
+    if let Err(e) = check_ordered_delivery(connection_config, conditions, channel_id, message_count) {
+        panic!("channel {channel_id} did not deliver the messages in order: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renet::DefaultChannel;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    fn tick(transport: &mut MockSteamTransport, server: &mut RenetServer) {
+        server.update(TICK);
+        transport.update(TICK, server);
+        transport.send_packets(server);
+    }
+
+    #[test]
+    fn laggy_client_among_good_ones() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut transport = MockSteamTransport::new(ConnectionConfig::default());
+        transport.connect(0, &mut server);
+        transport.connect(1, &mut server);
+        transport.set_conditions(
+            1,
+            NetworkSimulator {
+                latency: Duration::from_millis(200),
+                ..Default::default()
+            },
+        );
+
+        server.broadcast_message(DefaultChannel::ReliableOrdered, "hello");
+        transport.send_packets(&mut server);
+
+        tick(&mut transport, &mut server);
+        let good = transport.client_mut(0).unwrap().receive_message(DefaultChannel::ReliableOrdered);
+        assert_eq!(good.unwrap(), "hello");
+        assert!(transport
+            .client_mut(1)
+            .unwrap()
+            .receive_message(DefaultChannel::ReliableOrdered)
+            .is_none());
+
+        for _ in 0..20 {
+            tick(&mut transport, &mut server);
+        }
+        let laggy = transport.client_mut(1).unwrap().receive_message(DefaultChannel::ReliableOrdered);
+        assert_eq!(laggy.unwrap(), "hello");
+    }
+
+    #[test]
+    fn lossy_client_receives_nothing() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut transport = MockSteamTransport::new(ConnectionConfig::default());
+        transport.connect(0, &mut server);
+        transport.set_conditions(
+            0,
+            NetworkSimulator {
+                packet_loss: 1.0,
+                ..Default::default()
+            },
+        );
+
+        server.send_message(0, DefaultChannel::Unreliable, "lost");
+        transport.send_packets(&mut server);
+        tick(&mut transport, &mut server);
+
+        assert!(transport
+            .client_mut(0)
+            .unwrap()
+            .receive_message(DefaultChannel::Unreliable)
+            .is_none());
+    }
+
+    #[test]
+    fn same_seed_same_conditions() {
+        let conditions = NetworkSimulator {
+            jitter: Duration::from_millis(50),
+            packet_loss: 0.3,
+            seed: 42,
+            ..Default::default()
+        };
+
+        let mut state_a = conditions.seed;
+        let mut state_b = conditions.seed;
+        for _ in 0..100 {
+            assert_eq!(conditions.simulate(&mut state_a), conditions.simulate(&mut state_b));
+        }
+    }
+
+    fn bad_conditions(seed: u64) -> NetworkSimulator {
+        NetworkSimulator {
+            latency: Duration::from_millis(40),
+            jitter: Duration::from_millis(80),
+            packet_loss: 0.25,
+            seed,
+        }
+    }
+
+    #[test]
+    fn reliable_ordered_delivers_in_order() {
+        for seed in 0..5 {
+            assert_ordered_delivery(
+                ConnectionConfig::default(),
+                bad_conditions(seed),
+                DefaultChannel::ReliableOrdered.into(),
+                200,
+            );
+        }
+    }
+
+    #[test]
+    fn unordered_channels_fail_the_check() {
+        let config = ConnectionConfig::default();
+        let result = check_ordered_delivery(config.clone(), bad_conditions(1), DefaultChannel::ReliableUnordered.into(), 200);
+        assert!(matches!(result, Err(DeliveryError::OutOfOrder { .. })));
+
+        let result = check_ordered_delivery(config, bad_conditions(1), DefaultChannel::Unreliable.into(), 200);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn unknown_client_policy() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut transport = MockSteamTransport::new(ConnectionConfig::default());
+        transport.connect(0, &mut server);
+        // Added to the server without a transport connection
+        server.add_connection(1);
+
+        transport.send_packets(&mut server);
+        assert!(server.is_connected(1));
+        assert!(transport.unknown_clients().is_empty());
+
+        transport.set_unknown_client_policy(UnknownClientPolicy::Event);
+        transport.send_packets(&mut server);
+        assert_eq!(transport.unknown_clients(), vec![1]);
+        assert!(server.is_connected(1));
+
+        transport.set_unknown_client_policy(UnknownClientPolicy::Remove);
+        transport.send_packets(&mut server);
+        assert!(!server.is_connected(1));
+        assert!(server.is_connected(0));
+
+        transport.send_packets(&mut server);
+        assert!(transport.unknown_clients().is_empty());
+    }
+
+    fn received_messages(server: &mut RenetServer, client_id: ClientId, received: &mut Vec<Vec<u8>>) {
+        while let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableUnordered) {
+            received.push(message.to_vec());
+        }
+    }
+
+    #[test]
+    fn replay_recorded_session() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut transport = MockSteamTransport::new(ConnectionConfig::default());
+        transport.start_recording();
+        transport.connect(0, &mut server);
+        transport.set_conditions(0, bad_conditions(3));
+
+        let mut recorded = vec![];
+        for i in 0..50u32 {
+            let client = transport.client_mut(0).unwrap();
+            client.send_message(DefaultChannel::ReliableUnordered, i.to_le_bytes().to_vec());
+            tick(&mut transport, &mut server);
+            received_messages(&mut server, 0, &mut recorded);
+        }
+        for _ in 0..300 {
+            tick(&mut transport, &mut server);
+            received_messages(&mut server, 0, &mut recorded);
+        }
+        transport.disconnect_client(0, &mut server);
+        let recording = transport.take_recording().unwrap();
+        assert_eq!(recorded.len(), 50);
+
+        let recording = SessionRecording::from_bytes(&recording.to_bytes()).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut transport = MockSteamTransport::new(ConnectionConfig::default());
+        transport.replay(recording);
+
+        let mut replayed = vec![];
+        while !transport.is_replay_finished() {
+            tick(&mut transport, &mut server);
+            if server.is_connected(0) {
+                received_messages(&mut server, 0, &mut replayed);
+            }
+        }
+
+        assert_eq!(replayed, recorded);
+        assert!(!server.is_connected(0));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{fmt, time::Duration};
+
+use renet::ClientId;
+
+// Format version of the serialized recordings, increased when the format changes
+const RECORDING_VERSION: u8 = 1;
+
+/// What happened to a client in a recorded session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedEventKind {
+    /// The client was added to renet
+    Connected,
+    /// A renet packet received from the client, after the steam messages were decompressed and split
+    Packet(Vec<u8>),
+    /// The client was removed from renet
+    Disconnected,
+}
+
+/// Event of a recorded session, at the time since the recording started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    pub at: Duration,
+    pub client_id: ClientId,
+    pub kind: RecordedEventKind,
+}
+
+/// Connections and packets received by a server, in the order they were received,
+/// see [`SteamServerTransport::start_recording`][crate::SteamServerTransport::start_recording].
+///
+/// Saved with [`to_bytes`](Self::to_bytes) and replayed offline with
+/// [`MockSteamTransport::replay`][crate::MockSteamTransport::replay] to reproduce the packet sequence of a reported bug.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionRecording {
+    pub events: Vec<RecordedEvent>,
+}
+
+/// The recording is truncated, corrupted or from an unsupported format version, see [`SessionRecording::from_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidRecording;
+
+impl std::error::Error for InvalidRecording {}
+
+impl fmt::Display for InvalidRecording {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid session recording")
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], InvalidRecording> {
+        if self.bytes.len() < len {
+            return Err(InvalidRecording);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidRecording> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, InvalidRecording> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl SessionRecording {
+    pub(crate) fn push(&mut self, at: Duration, client_id: ClientId, kind: RecordedEventKind) {
+        self.events.push(RecordedEvent { at, client_id, kind });
+    }
+
+    /// Serializes the recording in a compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![RECORDING_VERSION];
+        bytes.extend_from_slice(&(self.events.len() as u64).to_le_bytes());
+        for event in self.events.iter() {
+            bytes.extend_from_slice(&(event.at.as_micros() as u64).to_le_bytes());
+            bytes.extend_from_slice(&event.client_id.to_le_bytes());
+            match &event.kind {
+                RecordedEventKind::Connected => bytes.push(0),
+                RecordedEventKind::Packet(packet) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&(packet.len() as u64).to_le_bytes());
+                    bytes.extend_from_slice(packet);
+                }
+                RecordedEventKind::Disconnected => bytes.push(2),
+            }
+        }
+
+        bytes
+    }
+
+    /// Deserializes a recording serialized with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidRecording> {
+        let mut reader = Reader { bytes };
+        if reader.u8()? != RECORDING_VERSION {
+            return Err(InvalidRecording);
+        }
+
+        let len = reader.u64()?;
+        let mut recording = SessionRecording::default();
+        for _ in 0..len {
+            let at = Duration::from_micros(reader.u64()?);
+            let client_id = reader.u64()?;
+            let kind = match reader.u8()? {
+                0 => RecordedEventKind::Connected,
+                1 => {
+                    let len = usize::try_from(reader.u64()?).map_err(|_| InvalidRecording)?;
+                    RecordedEventKind::Packet(reader.take(len)?.to_vec())
+                }
+                2 => RecordedEventKind::Disconnected,
+                _ => return Err(InvalidRecording),
+            };
+            recording.push(at, client_id, kind);
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(InvalidRecording);
+        }
+
+        Ok(recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_recording() {
+        let mut recording = SessionRecording::default();
+        recording.push(Duration::ZERO, 7, RecordedEventKind::Connected);
+        recording.push(Duration::from_millis(16), 7, RecordedEventKind::Packet(vec![1, 2, 3]));
+        recording.push(Duration::from_millis(32), 7, RecordedEventKind::Disconnected);
+
+        let bytes = recording.to_bytes();
+        assert_eq!(SessionRecording::from_bytes(&bytes), Ok(recording));
+        assert_eq!(SessionRecording::from_bytes(&bytes[..bytes.len() - 1]), Err(InvalidRecording));
+        assert_eq!(SessionRecording::from_bytes(&[0]), Err(InvalidRecording));
+    }
+}
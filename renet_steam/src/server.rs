@@ -1,12 +1,38 @@
-use std::{collections::{HashMap, HashSet}, net::{IpAddr, SocketAddr}};
+use std::{
+    any::TypeId,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use renet::{ClientId, RenetServer};
+use renet::{Bytes, ClientId, ConnectionConfig, PacketSizeError, ProcessError, RenetServer, ServerEvent};
 use steamworks::{
-    networking_sockets::{InvalidHandle, ListenSocket, NetConnection}, networking_types::{ListenSocketEvent, NetConnectionEnd, NetworkingConfigEntry, NetworkingMessage, SendFlags}, networking_utils::NetworkingUtils, Client, ClientManager, FriendFlags, Friends, LobbyId, Manager, Matchmaking, SteamId
+    networking_sockets::{InvalidHandle, ListenSocket, NetConnection, NetworkingSockets},
+    networking_types::{
+        ConnectionRequest, ListenSocketEvent, NetConnectionEnd, NetworkingAvailabilityResult, NetworkingConfigEntry, NetworkingConfigValue,
+        NetworkingMessage, SendFlags,
+    },
+    networking_utils::NetworkingUtils,
+    AuthSessionValidateError, CallbackHandle, Client, ClientManager, FriendFlags, Friends, LobbyId, Manager, Matchmaking,
+    PersonaStateChange, ServerManager, SteamError, SteamId, SteamServersConnected, SteamServersDisconnected, ValidateAuthTicketResponse,
 };
 
 use super::MAX_MESSAGE_BATCH_SIZE;
+use crate::{
+    disconnect::APP_DISCONNECT_CODES,
+    log_limiter::LogLimiter,
+    message::{self, TransportMessage},
+    recording::{RecordedEventKind, SessionRecording},
+    throughput::{Throughput, ThroughputDropConfig, ThroughputDropDetector, ThroughputWindow},
+};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AccessPermission {
     /// Everyone can connect
     Public,
@@ -20,79 +46,1148 @@ pub enum AccessPermission {
     InLobby(LobbyId),
 }
 
+/// What the transport does with a connection when steam fails to answer the query of a
+/// [`AccessPermission::FriendsOnly`] or [`AccessPermission::InLobby`] permission, see [`SteamServerConfig::degraded_access_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DegradedAccessPolicy {
+    /// The connection is rejected with [`RejectionReason::AccessCheckFailed`]
+    #[default]
+    FailClosed,
+    /// The connection is accepted and [`SteamServerEvent::AccessCheckDegraded`] is emitted
+    FailOpen,
+}
+
+/// What the transport does with a client connected to renet without a transport connection, see [`SteamServerConfig::unknown_client_policy`].
+///
+/// It happens when a connection is added to the [`RenetServer`] directly instead of by the transport,
+/// the host client of a [`listen server`](SteamServerConfig::listen_server) is expected and ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownClientPolicy {
+    /// The client is skipped and an error is logged
+    #[default]
+    Log,
+    /// The client is removed from renet, like a disconnected client
+    Remove,
+    /// The client is skipped and [`SteamServerEvent::UnknownClient`] is emitted, once until the client is removed from renet
+    Event,
+}
+
+/// Ban and allow lists of the server, see [`SteamServerTransport::export_lists`].
+///
+/// Serializable with the `serde` feature, so moderation state can be saved to disk and restored after a restart.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccessLists {
+    /// Raw steam ids of the banned users, sorted
+    pub banned: Vec<u64>,
+    /// Raw steam ids of the [`AccessPermission::InList`] users, sorted. None with another access permission
+    pub allowed: Option<Vec<u64>>,
+}
+
+/// Reason for the transport to reject a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The server reached the maximum number of clients
+    TooManyClients,
+    /// The connection did not have a valid steam id
+    InvalidSteamId,
+    /// The access permission does not allow the user to connect
+    NotAllowed,
+    /// Steam failed to answer the access permission query, see [`SteamServerConfig::degraded_access_policy`]
+    AccessCheckFailed,
+    /// The server reached the maximum number of spectators, see [`SteamServerConfig::max_spectators`]
+    TooManySpectators,
+    /// The user is banned from the server, see [`SteamServerTransport::ban`]
+    Banned,
+    /// The client id generator returned the id of a client already connected,
+    /// see [`SteamServerTransport::set_client_id_generator`]
+    DuplicateClientId { client_id: ClientId },
+    /// The host is not connected to the steam servers
+    SteamOffline,
+    /// The client version does not match [`SteamServerTransport::set_required_app_version`],
+    /// None if the client did not send its version in time
+    AppVersionMismatch { rejected_version: Option<u32> },
+}
+
+/// Status of the connection from the host to the steam servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteamStatus {
+    Online,
+    /// New connections are rejected until steam is back online
+    SteamOffline,
+}
+
+/// Events from the renet server and from the steam transport, see [`SteamServerTransport::poll_events`].
+#[derive(Debug)]
+pub enum SteamServerEvent {
+    /// Connection and disconnection events from the renet server
+    Renet(ServerEvent),
+    /// The transport rejected a connection
+    ConnectionRejected {
+        steam_id: Option<SteamId>,
+        reason: RejectionReason,
+    },
+    /// Steam failed to accept a permitted connection
+    AcceptFailed { steam_id: SteamId, error: SteamError },
+    /// The status of the access to the steam relay network changed
+    RelayNetworkStatusChanged { status: NetworkingAvailabilityResult },
+    /// The host logged out or lost the connection to the steam servers, or connected back
+    SteamStatusChanged { status: SteamStatus },
+    /// The client filled its receive queue, see [`SteamServerConfig::max_receive_queue_messages`]
+    ReceiveQueueFull { client_id: ClientId },
+    /// The client was disconnected after reaching its maximum lifetime, see [`SteamServerConfig::max_connection_lifetime`]
+    SessionExpired { client_id: ClientId },
+    /// The rate steam can send to the client dropped sharply, usually from congestion, see [`SteamServerConfig::throughput_drop`].
+    /// Rates are in kilobits per second.
+    ThroughputDrop { client_id: ClientId, from_kbps: f32, to_kbps: f32 },
+    /// Steam failed to answer the access permission query and the connection was accepted without it,
+    /// see [`DegradedAccessPolicy::FailOpen`]
+    AccessCheckDegraded { steam_id: SteamId },
+    /// The client is connected to renet without a transport connection, see [`UnknownClientPolicy::Event`]
+    UnknownClient { client_id: ClientId },
+}
+
+// Lanes of the connections, steam connections start with a single lane.
+// Sending on other lanes is not supported: choosing the lane of an outgoing message needs m_idxLane on the steam
+// message or the raw connection handle, and steamworks-rs 0.11 keeps both private.
+const CONNECTION_LANES: i32 = 1;
+
+// Time for a connection to send its version and class before being rejected or added as a player
+const APP_VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Connection waiting for the client version or class before being added to renet
+struct PendingConnection<Manager> {
+    connection: NetConnection<Manager>,
+    connected_at: Instant,
+    version: Option<u32>,
+    spectator: Option<bool>,
+}
+
+// Persona data of a connected client, None while steam has not fetched it
+#[derive(Debug, Default)]
+struct Persona {
+    name: Option<String>,
+    avatar_hash: Option<u64>,
+}
+
+// How the listen socket was created, so it can be created again when steam comes back online
+enum ListenSocketKind {
+    P2p {
+        options: Vec<NetworkingConfigEntry>,
+    },
+    Ip {
+        addr: SocketAddr,
+        options: Vec<NetworkingConfigEntry>,
+    },
+}
+
+/// Message received from a client, the data is only valid during the call that provides it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedMessage<'a> {
+    pub client_id: ClientId,
+    pub data: &'a [u8],
+}
+
+/// Result of the validation of a client auth ticket, see [`SteamServerTransport::drain_auth_events`].
+pub type AuthSessionResponse = Result<(), AuthSessionValidateError>;
+
+type PacketInspector = Box<dyn FnMut(ReceivedMessage) + Send + Sync>;
+type PerClientCallback = Box<dyn FnMut(ClientId, &mut RenetServer) + Send + Sync>;
+type ClientIdGenerator = Box<dyn FnMut(SteamId) -> ClientId + Send + Sync>;
+
+/// Order in which clients are served in [`SteamServerTransport::send_packets`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SendOrder {
+    /// Clients are served in the order returned by [`RenetServer::clients_id`]
+    #[default]
+    Unspecified,
+    /// The first client served rotates every call, so no client is always served last
+    RoundRobin,
+}
+
 pub struct SteamServerConfig {
+    /// Maximum number of clients, or of players when spectators are enabled, see [`Self::max_spectators`].
     pub max_clients: usize,
     pub access_permission: AccessPermission,
+    /// Maximum number of messages received from all clients in an [`update`](SteamServerTransport::update), no limit when None.
+    /// The remaining messages are received in the next updates, starting from the clients that were left out.
+    pub max_messages_per_update: Option<usize>,
+    /// Maximum number of reliable packets kept to be sent again in the next [`send_packets`](SteamServerTransport::send_packets)
+    /// when steam fails to send them due to a transient error, like a full send buffer. Retries are disabled when 0.
+    pub send_retry_buffer_size: usize,
+    /// Order in which clients are served when sending packets.
+    pub send_order: SendOrder,
+    /// Maximum number of steam messages queued in a [`send_packets`](SteamServerTransport::send_packets), no limit when None.
+    ///
+    /// Bounds the time spent in the send loop when the server sends many large snapshots. The messages over the budget are
+    /// sent first in the next calls, followed by the clients that were left out, their packets are generated only when they are served.
+    /// Deferred messages arrive at least one call later, so prefer a budget above the messages sent in a normal frame,
+    /// see [`SteamServerStats::sends_deferred`]. Unreliable messages of left out clients stay in their renet channels until then.
+    pub max_sends_per_update: Option<usize>,
+    /// Maximum number of received messages steam buffers for each connection waiting to be received by the transport,
+    /// messages above the limit are dropped by steam. Steam uses a limit of 1000 messages when None.
+    ///
+    /// The limit is set in the global steam networking configuration, so it also applies to other connections created afterwards.
+    /// When a client fills its queue, [`SteamServerEvent::ReceiveQueueFull`] is emitted so the server can disconnect it.
+    /// The queue is only checked when the transport can receive the whole queue in an update,
+    /// so [`Self::max_messages_per_update`] should be above this limit.
+    pub max_receive_queue_messages: Option<usize>,
+    /// Enables Nagle's algorithm for all sends, packets wait up to 5ms to be coalesced with the next ones
+    /// instead of being sent right away. Disabled by default: renet packets and transport messages are sent
+    /// without Nagle delay and the Nagle time of the connections is set to 0.
+    ///
+    /// Since renet already aggregates messages in packets per update, each packet is sent in its own steam message.
+    /// Enabling it lowers the overhead of headers and the packets on the wire, at the cost of latency.
+    /// Keep it disabled for competitive games where latency matters more than bandwidth.
+    pub enable_nagle: bool,
+    /// Maximum duration of a connection, no limit when None. Clients connected for longer are disconnected
+    /// in [`update`](SteamServerTransport::update) with [`SteamServerEvent::SessionExpired`],
+    /// useful to rebalance clients in a fleet of servers.
+    ///
+    /// The client is notified before the connection is closed, see [`SteamClientTransport::session_expired`][crate::SteamClientTransport::session_expired],
+    /// with the payload set in [`SteamServerTransport::set_session_redirect`] so it knows where to reconnect.
+    /// A client that reconnects to the same server starts a new session with a full lifetime.
+    pub max_connection_lifetime: Option<Duration>,
+    /// Maximum size in bytes of the steam messages that bundle the renet packets sent to a client in a
+    /// [`send_packets`](SteamServerTransport::send_packets), disabled when None. Packets that do not fit are sent alone.
+    ///
+    /// Fewer steam messages lower the per message overhead, but a lost message loses all its packets.
+    /// Batches are sent unreliable like single packets, renet already resends reliable messages on its own.
+    /// Use a size below the steam MTU so batches are not fragmented, see [`SteamServerTransport::max_packet_size`].
+    /// Clients split batches without any configuration, to coalesce the packets sent by the clients
+    /// see [`SteamClientTransport::set_coalesce_packets`][crate::SteamClientTransport::set_coalesce_packets].
+    pub coalesce_packets: Option<usize>,
+    /// Whether the server runs in the game of a player that also plays on it (listen server),
+    /// instead of a dedicated server. The host player uses the steam id of the server as its client id,
+    /// see [`SteamServerTransport::host_client_id`].
+    ///
+    /// The host player does not connect through steam, use [`RenetServer::new_local_client`] for it.
+    pub listen_server: bool,
+    /// Whether the steam messages sent to the clients are compressed with deflate, after the packets are coalesced.
+    /// Messages that do not get smaller are sent uncompressed.
+    ///
+    /// Lowers the relay bandwidth for big uncompressed data, like snapshots with many entities,
+    /// at the cost of CPU time for every message sent and received. It does not help with small packets
+    /// or with data the game already compresses, and only adds CPU time for them.
+    /// Run `cargo bench -p renet_steam --bench compression` to compare the trade-off for a payload.
+    /// Clients decompress messages without any configuration, to compress the packets sent by the clients
+    /// see [`SteamClientTransport::set_compress_packets`][crate::SteamClientTransport::set_compress_packets].
+    pub compress_packets: bool,
+    /// Maximum number of connection requests accepted in an [`update`](SteamServerTransport::update), unlimited when None.
+    /// The requests over the limit are kept and answered in the next updates, in the order they arrived,
+    /// see [`SteamServerStats::accepts_deferred`].
+    ///
+    /// Spreads the cost of the clients that connect at the same time, like after a map change, over several frames.
+    /// Steam times out requests that are not answered after a few seconds, so keep it high enough for the expected join rate.
+    pub max_accepts_per_update: Option<usize>,
+    /// Maximum number of spectators, connected beyond [`Self::max_clients`] with their own slots. Disabled when 0.
+    ///
+    /// Clients choose their class with [`SteamClientTransport::set_spectator`][crate::SteamClientTransport::set_spectator],
+    /// sent right after connecting. When enabled, clients are only added to renet once their class is received,
+    /// so the player and spectator limits are checked for the right class. Clients that send other messages without
+    /// a class first, or no message in time, are added as players. See [`SteamServerTransport::spectator_count`].
+    pub max_spectators: usize,
+    /// Reports [`SteamServerEvent::ThroughputDrop`] when the send rate of a client drops sharply, disabled when None.
+    ///
+    /// The rate is the one steam estimates it can send to the client, read from the connection status in each
+    /// [`update`](SteamServerTransport::update). Games can lower the snapshot fidelity of the client in response.
+    pub throughput_drop: Option<ThroughputDropConfig>,
+    /// Whether connections are accepted when steam fails to answer if the user is a friend of the host
+    /// or a member of the lobby, for the [`AccessPermission::FriendsOnly`] and [`AccessPermission::InLobby`] permissions.
+    ///
+    /// The query fails when the host is logged off from steam, or for [`AccessPermission::InLobby`] when steam
+    /// has no data for the lobby because the host is not a member of it. Users that are not friends of the host,
+    /// including hosts without friends, are rejected as not permitted. See [`SteamServerStats::access_checks_failed`].
+    pub degraded_access_policy: DegradedAccessPolicy,
+    /// Time steam waits without hearing from a connected client before closing its connection (`TimeoutConnected`),
+    /// the steam default of 10 seconds when None. Applies to the connections accepted by the server.
+    ///
+    /// Keep it shorter than the [`ConnectionConfig::reliable_timeout`] of renet, so a lost client is reported by steam as a
+    /// transport disconnection and [`DisconnectReason::ReliableTimeout`][renet::DisconnectReason::ReliableTimeout]
+    /// only disconnects clients that are still connected but stopped acknowledging.
+    /// Check it at startup with [`SteamServerTransport::validate_connection_timeout`].
+    pub connection_timeout: Option<Duration>,
+    /// Shares all the ICE candidates with the clients and penalizes the steam relays in the route selection of P2P connections,
+    /// so steam picks a direct route when one is available. Disabled by default.
+    /// This only configures the route selection of steam, connections of servers listening on an ip address are always direct.
+    ///
+    /// Steam keeps evaluating the routes while connected, so the ping and packet loss of a connection can change
+    /// mid-session when the route changes, see [`SteamServerTransport::connection_stats`].
+    /// The route itself can't be read, steamworks-rs 0.11 does not expose the flags of the connection info.
+    pub prefer_direct_connections: bool,
+    /// What [`SteamServerTransport::send_packets`] does with the clients of renet that have no steam connection,
+    /// logged by default.
+    pub unknown_client_policy: UnknownClientPolicy,
+}
+
+impl SteamServerConfig {
+    /// Config for the clients allowed by the access permission, the other options are disabled.
+    pub fn new(max_clients: usize, access_permission: AccessPermission) -> Self {
+        Self {
+            max_clients,
+            access_permission,
+            max_messages_per_update: None,
+            send_retry_buffer_size: 0,
+            send_order: SendOrder::default(),
+            max_sends_per_update: None,
+            max_receive_queue_messages: None,
+            enable_nagle: false,
+            max_connection_lifetime: None,
+            coalesce_packets: None,
+            listen_server: false,
+            compress_packets: false,
+            max_accepts_per_update: None,
+            max_spectators: 0,
+            throughput_drop: None,
+            degraded_access_policy: DegradedAccessPolicy::default(),
+            connection_timeout: None,
+            prefer_direct_connections: false,
+            unknown_client_policy: UnknownClientPolicy::default(),
+        }
+    }
+}
+
+/// Real-time status of the steam connection of a client, see [`SteamServerTransport::connection_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SteamConnectionStats {
+    /// Round trip time in milliseconds
+    pub ping: i32,
+    /// Fraction of the packets delivered, measured locally, from 0 to 1
+    pub quality_local: f32,
+    /// Fraction of the packets delivered, observed by the client, from 0 to 1
+    pub quality_remote: f32,
+    pub out_bytes_per_sec: f32,
+    pub in_bytes_per_sec: f32,
+    /// Rate steam estimates it can send to the client
+    pub send_rate_bytes_per_sec: i32,
+    /// Bytes queued by steam and not yet sent, reliable bytes include the ones waiting to be resent
+    pub pending_unreliable: i32,
+    pub pending_reliable: i32,
+    /// Reliable bytes sent and not yet acknowledged
+    pub sent_unacked_reliable: i32,
+}
+
+/// Bytes queued in a steam lane of a connection, see [`SteamServerTransport::lane_stats`].
+///
+/// Connections only have the single default lane.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LaneStats {
+    /// Bytes queued and not yet sent, reliable bytes include the ones waiting to be resent
+    pub pending_unreliable: i32,
+    pub pending_reliable: i32,
+    /// Reliable bytes sent and not yet acknowledged
+    pub sent_unacked_reliable: i32,
+    /// Time steam predicts the data queued in the lane waits before being sent, with the lane priorities and weights
+    pub queue_time: Duration,
+}
+
+/// Steam waits longer than renet before giving up on a silent connection,
+/// see [`SteamServerTransport::validate_connection_timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutMismatch {
+    /// Time steam waits for a connected client before closing the connection
+    pub steam_timeout: Duration,
+    /// Time renet resends an unacknowledged reliable message before disconnecting, see [`ConnectionConfig::reliable_timeout`]
+    pub reliable_timeout: Duration,
+}
+
+impl std::error::Error for TimeoutMismatch {}
+
+impl fmt::Display for TimeoutMismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "steam times out connections after {:?} but renet gives up on reliable messages after {:?}",
+            self.steam_timeout, self.reliable_timeout
+        )
+    }
+}
+
+/// Statistics of the steam server transport, see [`SteamServerTransport::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SteamServerStats {
+    /// Number of messages received in the last update
+    pub messages_received: usize,
+    /// Whether the last update reached [`SteamServerConfig::max_messages_per_update`]
+    pub receive_budget_exhausted: bool,
+    /// Number of updates that reached [`SteamServerConfig::max_messages_per_update`]
+    pub receive_budget_exhausted_count: u64,
+    /// Number of packets that failed to send and were queued to be sent again
+    pub send_retries: u64,
+    /// Number of packets that failed to send and were dropped because the retry buffer was full
+    pub send_retries_dropped: u64,
+    /// Number of times a client filled its receive queue
+    pub receive_queue_full: u64,
+    /// Total bytes sent to the clients
+    pub bytes_sent: u64,
+    /// Total bytes received from the clients
+    pub bytes_received: u64,
+    /// Number of connection requests deferred in the last update because of [`SteamServerConfig::max_accepts_per_update`]
+    pub accepts_deferred: usize,
+    /// Number of messages kept for the next send because of [`SteamServerConfig::max_sends_per_update`]
+    pub sends_deferred: usize,
+    /// Number of clients left out of the last send because of [`SteamServerConfig::max_sends_per_update`]
+    pub clients_deferred: usize,
+    /// Number of connection requests for which steam failed to answer the access permission query,
+    /// see [`SteamServerConfig::degraded_access_policy`]
+    pub access_checks_failed: u64,
+    /// Number of received packets that renet failed to deserialize, usually from a modified client
+    pub malformed_packets: u64,
+    /// Number of received packets dropped by renet because they were already received
+    pub duplicate_packets: u64,
+    /// Number of received packets dropped by renet because they were older than the tracked packets
+    pub out_of_window_packets: u64,
 }
 
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::resource::Resource))]
 pub struct SteamServerTransport<Manager = ClientManager> {
     listen_socket: ListenSocket<Manager>,
+    listen_socket_kind: ListenSocketKind,
+    sockets: NetworkingSockets<Manager>,
     utils: NetworkingUtils<Manager>,
     matchmaking: Matchmaking<Manager>,
     friends: Friends<Manager>,
     max_clients: usize,
+    max_spectators: usize,
+    spectators: HashSet<ClientId>,
     access_permission: AccessPermission,
+    degraded_access_policy: DegradedAccessPolicy,
+    unknown_client_policy: UnknownClientPolicy,
+    unknown_clients: HashSet<ClientId>,
+    banned: HashSet<SteamId>,
+    max_messages_per_update: Option<usize>,
+    max_receive_queue_messages: Option<usize>,
+    receive_order: Vec<ClientId>,
+    next_receive_index: usize,
+    stats: SteamServerStats,
+    throughput: ThroughputWindow,
+    throughput_drop: Option<ThroughputDropConfig>,
+    throughput_drop_detectors: HashMap<ClientId, ThroughputDropDetector>,
+    buffer_received: bool,
+    received: Vec<(ClientId, Bytes)>,
+    packet_inspector: Option<PacketInspector>,
+    recording: Option<(Instant, SessionRecording)>,
+    per_client_callback: Option<PerClientCallback>,
+    // Steam ids are used as client ids when None
+    client_id_generator: Option<ClientIdGenerator>,
+    steam_ids: HashMap<ClientId, SteamId>,
+    client_ids: HashMap<SteamId, ClientId>,
+    muted_clients: HashSet<ClientId>,
     connections: HashMap<ClientId, NetConnection<Manager>>,
-    messages: Vec<NetworkingMessage<Manager>>
+    required_app_version: Option<u32>,
+    pending_connections: HashMap<ClientId, PendingConnection<Manager>>,
+    // Connections accepted that steam has not connected yet
+    accepted: HashMap<SteamId, Instant>,
+    max_accepts_per_update: Option<usize>,
+    deferred_requests: VecDeque<ConnectionRequest<Manager>>,
+    messages: Vec<NetworkingMessage<Manager>>,
+    // Client and packet copy for each message in `messages`, the packet is only kept when it can be retried
+    queued_packets: Vec<(ClientId, Option<Vec<u8>>)>,
+    send_retry_buffer_size: usize,
+    send_retries: Vec<(ClientId, Vec<u8>)>,
+    send_order: SendOrder,
+    next_send_index: usize,
+    max_sends_per_update: Option<usize>,
+    // Packets over the send budget with whether they can be retried, and the clients that were not served
+    deferred_sends: VecDeque<(ClientId, Vec<u8>, bool)>,
+    deferred_clients: HashSet<ClientId>,
+    enable_nagle: bool,
+    connection_timeout: Option<Duration>,
+    coalesce_packets: Option<usize>,
+    compress_packets: bool,
+    max_connection_lifetime: Option<Duration>,
+    session_redirect: Vec<u8>,
+    connected_at: HashMap<ClientId, Instant>,
+    events: VecDeque<SteamServerEvent>,
+    relay_network_status: NetworkingAvailabilityResult,
+    log_limiter: LogLimiter,
+    local_addr: Option<SocketAddr>,
+    local_steam_id: SteamId,
+    listen_server: bool,
+    steam_status: SteamStatus,
+    logged_on: Arc<AtomicBool>,
+    recreate_listen_socket: bool,
+    personas: HashMap<ClientId, Persona>,
+    persona_changes: Arc<Mutex<Vec<SteamId>>>,
+    auth_events: Arc<Mutex<Vec<(SteamId, AuthSessionResponse)>>>,
+    steam_callbacks: [CallbackHandle<Manager>; 4],
 }
 
 impl<T: Manager + 'static> SteamServerTransport<T> {
     pub fn new(client: &Client<T>, config: SteamServerConfig) -> Result<Self, InvalidHandle> {
-        let options: Vec<NetworkingConfigEntry> = Vec::new();
-        let listen_socket = client.networking_sockets().create_listen_socket_p2p(0, options)?;
-        let matchmaking = client.matchmaking();
-        let friends = client.friends();
-        let utils = client.networking_utils();
-        let messages = vec![];
+        set_receive_queue_limit(&config);
+        let options = listen_socket_options(&config, Vec::new());
+        let listen_socket = client.networking_sockets().create_listen_socket_p2p(0, options.clone())?;
 
-        Ok(Self {
+        Ok(Self::from_listen_socket(
+            client,
+            config,
             listen_socket,
-            utils,
-            messages,
-            matchmaking,
-            friends,
-            max_clients: config.max_clients,
-            access_permission: config.access_permission,
-            connections: HashMap::new(),
-        })
+            ListenSocketKind::P2p { options },
+            None,
+        ))
     }
 
-    pub fn new_ip(client: &Client<T>, config: SteamServerConfig, ip: IpAddr, port: u16, options: Vec<NetworkingConfigEntry>) -> Result<Self, InvalidHandle> {
-        let server_addr = SocketAddr::new(ip.into(), port);
-        let listen_socket = client.networking_sockets().create_listen_socket_ip(server_addr, options)?;
+    /// Creates a server listening on the ip address and port.
+    ///
+    /// When the port is 0, steam binds the listen socket to an ephemeral port, see [`Self::local_addr`].
+    pub fn new_ip(
+        client: &Client<T>,
+        config: SteamServerConfig,
+        ip: IpAddr,
+        port: u16,
+        options: Vec<NetworkingConfigEntry>,
+    ) -> Result<Self, InvalidHandle> {
+        let server_addr = SocketAddr::new(ip, port);
+        set_receive_queue_limit(&config);
+        let options = listen_socket_options(&config, options);
+        let listen_socket = client.networking_sockets().create_listen_socket_ip(server_addr, options.clone())?;
+        let kind = ListenSocketKind::Ip {
+            addr: server_addr,
+            options,
+        };
+
+        let local_addr = (port != 0).then_some(server_addr);
+        Ok(Self::from_listen_socket(client, config, listen_socket, kind, local_addr))
+    }
+
+    fn from_listen_socket(
+        client: &Client<T>,
+        config: SteamServerConfig,
+        listen_socket: ListenSocket<T>,
+        listen_socket_kind: ListenSocketKind,
+        local_addr: Option<SocketAddr>,
+    ) -> Self {
         let matchmaking = client.matchmaking();
         let friends = client.friends();
         let utils = client.networking_utils();
-        let messages = vec![];
+        let relay_network_status = utils.relay_network_status();
 
-        Ok(Self {
+        // The callbacks run with the steam callbacks, the status is checked in the next update
+        let logged_on = Arc::new(AtomicBool::new(client.user().logged_on()));
+        let disconnected = logged_on.clone();
+        let connected = logged_on.clone();
+        let persona_changes: Arc<Mutex<Vec<SteamId>>> = Arc::default();
+        let changes = persona_changes.clone();
+        let auth_events: Arc<Mutex<Vec<(SteamId, AuthSessionResponse)>>> = Arc::default();
+        let auth_responses = auth_events.clone();
+        let steam_callbacks = [
+            client.register_callback(move |event: SteamServersDisconnected| {
+                log::warn!("Lost connection to the steam servers: {}", event.reason);
+                disconnected.store(false, Ordering::Relaxed);
+            }),
+            client.register_callback(move |_: SteamServersConnected| connected.store(true, Ordering::Relaxed)),
+            client.register_callback(move |event: PersonaStateChange| changes.lock().unwrap().push(event.steam_id)),
+            client.register_callback(move |event: ValidateAuthTicketResponse| {
+                auth_responses.lock().unwrap().push((event.steam_id, event.response))
+            }),
+        ];
+        let steam_status = if logged_on.load(Ordering::Relaxed) { SteamStatus::Online } else { SteamStatus::SteamOffline };
+
+        Self {
             listen_socket,
+            listen_socket_kind,
+            sockets: client.networking_sockets(),
             utils,
-            messages,
+            messages: vec![],
+            queued_packets: vec![],
+            send_retry_buffer_size: config.send_retry_buffer_size,
+            send_retries: vec![],
+            send_order: config.send_order,
+            max_sends_per_update: config.max_sends_per_update,
+            deferred_sends: VecDeque::new(),
+            deferred_clients: HashSet::new(),
+            next_send_index: 0,
+            enable_nagle: config.enable_nagle,
+            connection_timeout: config.connection_timeout,
+            coalesce_packets: config.coalesce_packets,
+            compress_packets: config.compress_packets,
+            max_accepts_per_update: config.max_accepts_per_update,
+            deferred_requests: VecDeque::new(),
+            max_connection_lifetime: config.max_connection_lifetime,
+            session_redirect: Vec::new(),
+            connected_at: HashMap::new(),
             matchmaking,
             friends,
             max_clients: config.max_clients,
+            max_spectators: config.max_spectators,
+            spectators: HashSet::new(),
             access_permission: config.access_permission,
+            degraded_access_policy: config.degraded_access_policy,
+            unknown_client_policy: config.unknown_client_policy,
+            unknown_clients: HashSet::new(),
+            banned: HashSet::new(),
+            max_messages_per_update: config.max_messages_per_update,
+            max_receive_queue_messages: config.max_receive_queue_messages,
+            receive_order: Vec::new(),
+            next_receive_index: 0,
+            stats: SteamServerStats::default(),
+            throughput: ThroughputWindow::new(Duration::from_secs(1)),
+            throughput_drop: config.throughput_drop,
+            throughput_drop_detectors: HashMap::new(),
+            buffer_received: false,
+            received: Vec::new(),
+            packet_inspector: None,
+            recording: None,
+            per_client_callback: None,
+            client_id_generator: None,
+            steam_ids: HashMap::new(),
+            client_ids: HashMap::new(),
+            muted_clients: HashSet::new(),
             connections: HashMap::new(),
+            required_app_version: None,
+            pending_connections: HashMap::new(),
+            accepted: HashMap::new(),
+            events: VecDeque::new(),
+            relay_network_status,
+            log_limiter: LogLimiter::default(),
+            local_addr,
+            local_steam_id: client.user().steam_id(),
+            listen_server: config.listen_server,
+            steam_status,
+            logged_on,
+            recreate_listen_socket: false,
+            personas: HashMap::new(),
+            persona_changes,
+            auth_events,
+            steam_callbacks,
+        }
+    }
+
+    /// Returns the address the listen socket is bound to, for servers created with [`Self::new_ip`].
+    /// Returns None for peer-to-peer servers.
+    ///
+    /// When bound to port 0, the address is None until the first client connects:
+    /// steamworks-rs does not expose the handle of the listen socket, it is read back from steam
+    /// with the handle reported in the connection info of the first connection.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns the steam id of the user running the server.
+    pub fn local_steam_id(&self) -> SteamId {
+        self.local_steam_id
+    }
+
+    /// Returns whether the server was created as a listen server, with a local player,
+    /// see [`SteamServerConfig::listen_server`].
+    pub fn is_listen_server(&self) -> bool {
+        self.listen_server
+    }
+
+    /// Returns the client id of the host player for listen servers, the raw [`Self::local_steam_id`].
+    /// Returns None for dedicated servers.
+    pub fn host_client_id(&self) -> Option<ClientId> {
+        self.listen_server.then(|| self.local_steam_id.raw())
+    }
+
+    /// Returns whether the host is connected to the steam servers.
+    pub fn steam_status(&self) -> SteamStatus {
+        self.steam_status
+    }
+
+    /// When enabled, the listen socket is created again when steam comes back online,
+    /// disconnecting all the clients from the previous socket. Disabled by default.
+    pub fn set_recreate_listen_socket(&mut self, recreate_listen_socket: bool) {
+        self.recreate_listen_socket = recreate_listen_socket;
+    }
+
+    /// Returns the statistics of the transport.
+    pub fn stats(&self) -> &SteamServerStats {
+        &self.stats
+    }
+
+    /// Returns the steam persona name of the client, cached when the client connected.
+    ///
+    /// Returns None until steam has fetched the persona data of the user, and the name can change during the session,
+    /// so read it again instead of storing it.
+    pub fn persona_name(&self, client_id: ClientId) -> Option<String> {
+        self.personas.get(&client_id)?.name.clone()
+    }
+
+    /// Returns a hash of the small avatar of the client, cached when the client connected.
+    /// The hash changes when the user changes the avatar, use it to know when to fetch the avatar image again.
+    ///
+    /// Returns None until steam has fetched the persona data of the user, or if the user has no avatar.
+    pub fn avatar_hash(&self, client_id: ClientId) -> Option<u64> {
+        self.personas.get(&client_id)?.avatar_hash
+    }
+
+    /// Returns the auth session responses received from steam since the last call, in the order they were received.
+    ///
+    /// Steam sends a response when the ticket of an auth session is validated and again every time the status changes
+    /// during the session, for example when the user is VAC banned while playing or the ticket is cancelled,
+    /// so anti-cheat enforcement should keep draining it for the whole match.
+    /// The transport does not start auth sessions, responses are only received for the sessions started by the game
+    /// with [`User::begin_authentication_session`][steamworks::User::begin_authentication_session].
+    /// Responses for steam ids that are not connected to the transport are dropped.
+    pub fn drain_auth_events(&mut self) -> impl Iterator<Item = (ClientId, AuthSessionResponse)> + '_ {
+        let auth_events = std::mem::take(&mut *self.auth_events.lock().unwrap());
+        auth_events
+            .into_iter()
+            .filter_map(|(steam_id, response)| Some((*self.client_ids.get(&steam_id)?, response)))
+    }
+
+    /// Sets the lobby metadata for the key, visible to the lobby members and in lobby searches.
+    /// Returns false if the host is not the owner of the lobby or the data could not be set.
+    pub fn set_lobby_data(&self, lobby_id: LobbyId, key: &str, value: &str) -> bool {
+        self.matchmaking.set_lobby_data(lobby_id, key, value)
+    }
+
+    /// Returns the lobby metadata for the key, or None if it is not set.
+    pub fn lobby_data(&self, lobby_id: LobbyId, key: &str) -> Option<String> {
+        self.matchmaking.lobby_data(lobby_id, key).map(String::from)
+    }
+
+    fn fetch_persona(&self, steam_id: SteamId) -> Persona {
+        // Returns true when the data needs to be downloaded, we are notified with a PersonaStateChange
+        if self.friends.request_user_information(steam_id, false) {
+            return Persona::default();
+        }
+
+        let friend = self.friends.get_friend(steam_id);
+        let avatar_hash = friend.small_avatar().map(|avatar| {
+            let mut hasher = DefaultHasher::new();
+            avatar.hash(&mut hasher);
+            hasher.finish()
+        });
+
+        Persona {
+            name: Some(friend.name()),
+            avatar_hash,
+        }
+    }
+
+    fn remove_client_data(&mut self, client_id: ClientId) {
+        if let Some(steam_id) = self.steam_ids.remove(&client_id) {
+            self.client_ids.remove(&steam_id);
+        }
+        self.log_limiter.remove_client(client_id);
+        self.personas.remove(&client_id);
+        self.muted_clients.remove(&client_id);
+        if self.connected_at.remove(&client_id).is_some() {
+            self.record(client_id, RecordedEventKind::Disconnected);
+        }
+        self.spectators.remove(&client_id);
+        self.throughput_drop_detectors.remove(&client_id);
+    }
+
+    /// Sets the payload sent to clients when their session expires, for example the address of another server,
+    /// see [`SteamServerConfig::max_connection_lifetime`]. Empty by default.
+    pub fn set_session_redirect(&mut self, redirect: Vec<u8>) {
+        self.session_redirect = redirect;
+    }
+
+    /// When enabled, packets received in [`update`](Self::update) are buffered instead of being processed by renet,
+    /// use [`drain_received`](Self::drain_received) to take them and process them with [`RenetServer::process_packet_from`].
+    /// Disabled by default.
+    pub fn set_buffer_received(&mut self, buffer_received: bool) {
+        self.buffer_received = buffer_received;
+    }
+
+    /// Calls the function with all the packets buffered since the last call, see [`set_buffer_received`](Self::set_buffer_received).
+    ///
+    /// The buffer grows with every update until drained, so drain it every frame. Use
+    /// [`SteamServerConfig::max_messages_per_update`] to bound how many packets a single update can buffer.
+    /// Packets from clients that disconnected meanwhile will return an error when processed.
+    ///
+    /// # Usage
+    /// ```ignore
+    /// // Duplicate and out of window packets are expected on unreliable networks
+    /// transport.drain_received(|message| match server.process_packet_from(message.data, message.client_id) {
+    ///     Ok(()) | Err(ProcessError::Duplicate | ProcessError::OutOfWindow) => {}
+    ///     Err(e) => log::error!("Error while processing payload for {}: {}", message.client_id, e),
+    /// });
+    /// ```
+    pub fn drain_received(&mut self, mut f: impl FnMut(ReceivedMessage)) {
+        for (client_id, data) in self.received.drain(..) {
+            f(ReceivedMessage { client_id, data: &data });
+        }
+    }
+
+    /// Stops processing the packets received from the client until [`unmute_client`](Self::unmute_client),
+    /// without disconnecting it. Packets from muted clients are received and discarded, not buffered.
+    pub fn mute_client(&mut self, client_id: ClientId) {
+        if self.connections.contains_key(&client_id) {
+            self.muted_clients.insert(client_id);
+        }
+    }
+
+    /// Resumes processing the packets received from the client.
+    pub fn unmute_client(&mut self, client_id: ClientId) {
+        self.muted_clients.remove(&client_id);
+    }
+
+    pub fn is_client_muted(&self, client_id: ClientId) -> bool {
+        self.muted_clients.contains(&client_id)
+    }
+
+    /// Sets a function called with every packet received from the clients in [`update`](Self::update),
+    /// before it is processed by renet or buffered.
+    pub fn set_packet_inspector(&mut self, inspector: impl FnMut(ReceivedMessage) + Send + Sync + 'static) {
+        self.packet_inspector = Some(Box::new(inspector));
+    }
+
+    /// Removes the function set with [`set_packet_inspector`](Self::set_packet_inspector).
+    pub fn clear_packet_inspector(&mut self) {
+        self.packet_inspector = None;
+    }
+
+    /// Starts recording the clients connected and the packets received from them, replacing the current recording.
+    /// Packets from muted clients are not recorded.
+    ///
+    /// The recording can be replayed with [`MockSteamTransport::replay`][crate::MockSteamTransport::replay]
+    /// to reproduce the session without steam, its size grows with every packet received until taken.
+    pub fn start_recording(&mut self) {
+        self.recording = Some((Instant::now(), SessionRecording::default()));
+    }
+
+    /// Stops the recording and returns it, or None if [`start_recording`](Self::start_recording) was not called.
+    pub fn take_recording(&mut self) -> Option<SessionRecording> {
+        self.recording.take().map(|(_, recording)| recording)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    fn record(&mut self, client_id: ClientId, kind: RecordedEventKind) {
+        if let Some((started_at, recording)) = &mut self.recording {
+            recording.push(started_at.elapsed(), client_id, kind);
+        }
+    }
+
+    /// Sets a function called in [`update`](Self::update) once for each client served, right after its packets were processed,
+    /// so its messages can be read from the server. Muted clients and clients skipped by the
+    /// [`max_messages_per_update`](SteamServerConfig::max_messages_per_update) budget are not called.
+    ///
+    /// The transport is borrowed during the call, so only the [`RenetServer`] is available.
+    /// Do not add or remove connections of the server from the function, the transport owns them,
+    /// collect the clients to disconnect and call [`disconnect_client`](Self::disconnect_client) after the update instead.
+    pub fn set_per_client_callback(&mut self, callback: impl FnMut(ClientId, &mut RenetServer) + Send + Sync + 'static) {
+        self.per_client_callback = Some(Box::new(callback));
+    }
+
+    /// Removes the function set with [`set_per_client_callback`](Self::set_per_client_callback).
+    pub fn clear_per_client_callback(&mut self) {
+        self.per_client_callback = None;
+    }
+
+    /// Sets the function that gives the renet client id of a steam user when it connects, the steam id is used by default.
+    ///
+    /// Useful for deployments with many servers that need ids unique across them, by hashing the steam id
+    /// or by drawing ids from an allocator shared by the servers. Ids are sequential with:
+    ///
+    /// ```ignore
+    /// let mut next_client_id = 0;
+    /// transport.set_client_id_generator(move |_| {
+    ///     next_client_id += 1;
+    ///     next_client_id
+    /// });
+    /// ```
+    ///
+    /// The id must not be in use by another client, the connection is rejected with
+    /// [`RejectionReason::DuplicateClientId`] otherwise. Use [`steam_id`](Self::steam_id) and [`client_id`](Self::client_id)
+    /// to map between them, [`SteamClientTransport::client_id`][crate::SteamClientTransport::client_id] only matches the default ids.
+    /// It is not used for the [`host_client_id`](Self::host_client_id).
+    pub fn set_client_id_generator(&mut self, generator: impl FnMut(SteamId) -> ClientId + Send + Sync + 'static) {
+        self.client_id_generator = Some(Box::new(generator));
+    }
+
+    /// Uses the steam ids as client ids again for the next connections, see [`set_client_id_generator`](Self::set_client_id_generator).
+    pub fn clear_client_id_generator(&mut self) {
+        self.client_id_generator = None;
+    }
+
+    /// Returns the steam id of a connected or connecting client.
+    pub fn steam_id(&self, client_id: ClientId) -> Option<SteamId> {
+        self.steam_ids.get(&client_id).copied()
+    }
+
+    /// Returns the client id of a connected or connecting steam user.
+    pub fn client_id(&self, steam_id: SteamId) -> Option<ClientId> {
+        self.client_ids.get(&steam_id).copied()
+    }
+
+    /// Only accepts clients that send this version with [`SteamClientTransport::set_app_version`][crate::SteamClientTransport::set_app_version],
+    /// no version is required by default.
+    ///
+    /// Steam has no handshake payload, so the version is the first message from the client. Connections are only
+    /// added to renet after sending the right version, rejected clients never reach the server and
+    /// are reported with [`RejectionReason::AppVersionMismatch`].
+    pub fn set_required_app_version(&mut self, version: Option<u32>) {
+        self.required_app_version = version;
+    }
+
+    /// Returns the maximum size in bytes of the renet packets steam sends without fragmenting them,
+    /// or None if steam did not report its MTU.
+    pub fn max_packet_size(&self) -> Option<usize> {
+        crate::mtu_data_size()
+    }
+
+    /// Checks that the packets generated with the connection config are not fragmented by steam,
+    /// see [`ConnectionConfig::validate_packet_size`].
+    pub fn validate_packet_size(&self, connection_config: &ConnectionConfig) -> Result<(), PacketSizeError> {
+        crate::validate_packet_size(connection_config)
+    }
+
+    /// Returns the real-time status of the steam connection of the client,
+    /// None if the client is not connected or steam failed to return it.
+    pub fn connection_stats(&self, client_id: ClientId) -> Option<SteamConnectionStats> {
+        let connection = self.connections.get(&client_id)?;
+        let (info, _) = self.sockets.get_realtime_connection_status(connection, 0).ok()?;
+        Some(SteamConnectionStats {
+            ping: info.ping(),
+            quality_local: info.connection_quality_local(),
+            quality_remote: info.connection_quality_remote(),
+            out_bytes_per_sec: info.out_bytes_per_sec(),
+            in_bytes_per_sec: info.in_bytes_per_sec(),
+            send_rate_bytes_per_sec: info.send_rate_bytes_per_sec(),
+            pending_unreliable: info.pending_unreliable(),
+            pending_reliable: info.pending_reliable(),
+            sent_unacked_reliable: info.sent_unacked_reliable(),
         })
     }
 
+    /// Returns the bytes queued in the steam lanes of the connection of the client.
+    /// Empty if the client is not connected or steam failed to return them.
+    ///
+    /// Connections only have the single default lane, since the transport can't send messages on other lanes.
+    /// The returned vector has one element, with the same bytes as the [`connection_stats`](Self::connection_stats)
+    /// and the queue time of the connection.
+    pub fn lane_stats(&self, client_id: ClientId) -> Vec<LaneStats> {
+        let Some(connection) = self.connections.get(&client_id) else {
+            return Vec::new();
+        };
+        let Ok((_, lanes)) = self.sockets.get_realtime_connection_status(connection, CONNECTION_LANES) else {
+            return Vec::new();
+        };
+
+        lanes
+            .iter()
+            .map(|lane| LaneStats {
+                pending_unreliable: lane.pending_unreliable(),
+                pending_reliable: lane.pending_reliable(),
+                sent_unacked_reliable: lane.sent_unacked_reliable(),
+                // queued_send_bytes returns m_usecQueueTime of SteamNetConnectionRealTimeLaneStatus_t,
+                // the queue time of the lane in microseconds despite its name
+                queue_time: Duration::from_micros(lane.queued_send_bytes().max(0) as u64),
+            })
+            .collect()
+    }
+
+    /// Returns the [`connection_stats`](Self::connection_stats) of all connected clients.
+    pub fn all_connection_stats(&self) -> Vec<(ClientId, SteamConnectionStats)> {
+        self.connections
+            .keys()
+            .filter_map(|&client_id| Some((client_id, self.connection_stats(client_id)?)))
+            .collect()
+    }
+
+    /// Returns the [`connection_stats`](Self::connection_stats) of the given clients, in the same order,
+    /// like the clients near a player. Clients that are not connected are skipped.
+    pub fn connection_stats_for(&self, client_ids: &[ClientId]) -> Vec<(ClientId, SteamConnectionStats)> {
+        client_ids
+            .iter()
+            .filter_map(|&client_id| Some((client_id, self.connection_stats(client_id)?)))
+            .collect()
+    }
+
+    /// Checks that steam closes silent connections before renet disconnects them for unacknowledged reliable messages,
+    /// see [`SteamServerConfig::connection_timeout`]. Meant to be checked at startup, nothing is checked
+    /// without [`ConnectionConfig::max_retransmits`] or if steam did not report its timeout.
+    ///
+    /// Renet has no keep-alive of its own, the reliable timeout is the only way it gives up on a silent connection.
+    pub fn validate_connection_timeout(&self, connection_config: &ConnectionConfig) -> Result<(), TimeoutMismatch> {
+        let Some(reliable_timeout) = connection_config.reliable_timeout() else {
+            return Ok(());
+        };
+        let Some(steam_timeout) = self.connection_timeout.or_else(crate::connection_timeout) else {
+            log::warn!("Could not read the steam connection timeout, it was not validated against renet");
+            return Ok(());
+        };
+
+        if steam_timeout >= reliable_timeout {
+            return Err(TimeoutMismatch {
+                steam_timeout,
+                reliable_timeout,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the bytes per second sent and received during the throughput window,
+    /// updated in every [`update`](Self::update) and [`send_packets`](Self::send_packets).
+    pub fn throughput(&self) -> Throughput {
+        self.throughput.throughput(Instant::now())
+    }
+
+    /// Sets the duration of the window used to compute the [`throughput`](Self::throughput), one second by default.
+    pub fn set_throughput_window(&mut self, window: Duration) {
+        self.throughput.set_window(window);
+    }
+
     pub fn max_clients(&self) -> usize {
         self.max_clients
     }
 
+    pub fn max_spectators(&self) -> usize {
+        self.max_spectators
+    }
+
+    /// Returns the number of connected clients that are not spectators, including local clients.
+    pub fn player_count(&self, server: &RenetServer) -> usize {
+        server.connected_clients().saturating_sub(self.spectators.len())
+    }
+
+    /// Returns the half-open connections with the time they reached their current stage: the connections accepted
+    /// that steam has not connected yet, with the time they were accepted, and the connected ones that wait for their
+    /// client version or class before being added to renet, with the time they connected.
+    ///
+    /// Useful to diagnose handshakes and relay routes that get stuck, the connections become renet clients once complete.
+    pub fn pending_connections(&self) -> impl Iterator<Item = (SteamId, Instant)> + '_ {
+        let accepted = self.accepted.iter().map(|(&steam_id, &accepted_at)| (steam_id, accepted_at));
+        let waiting = self
+            .pending_connections
+            .iter()
+            .filter_map(|(client_id, pending)| Some((*self.steam_ids.get(client_id)?, pending.connected_at)));
+        accepted.chain(waiting)
+    }
+
+    /// Returns the number of connected spectators, see [`SteamServerConfig::max_spectators`].
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Returns whether the client connected as a spectator.
+    pub fn is_spectator(&self, client_id: ClientId) -> bool {
+        self.spectators.contains(&client_id)
+    }
+
     /// Update the access permission to the server,
     /// this change only applies to new connections.
     pub fn set_access_permissions(&mut self, access_permission: AccessPermission) {
         self.access_permission = access_permission;
     }
 
+    /// Returns the access permission checked for new connections.
+    pub fn access_permission(&self) -> &AccessPermission {
+        &self.access_permission
+    }
+
+    /// Returns the number of users allowed by an [`AccessPermission::InList`] permission or the number of members
+    /// of the lobby of an [`AccessPermission::InLobby`] permission, None with the other permissions.
+    pub fn permitted_users_count(&self) -> Option<usize> {
+        match &self.access_permission {
+            AccessPermission::InList(list) => Some(list.len()),
+            AccessPermission::InLobby(lobby_id) => Some(self.matchmaking.lobby_member_count(*lobby_id)),
+            AccessPermission::Public | AccessPermission::Private | AccessPermission::FriendsOnly => None,
+        }
+    }
+
+    /// Rejects the new connections of the user, checked before the access permission.
+    /// A connected client is not disconnected, use [`disconnect_client`](Self::disconnect_client) for it.
+    pub fn ban(&mut self, steam_id: SteamId) {
+        self.banned.insert(steam_id);
+    }
+
+    /// Allows the user to connect again, returns false if the user was not banned.
+    pub fn unban(&mut self, steam_id: SteamId) -> bool {
+        self.banned.remove(&steam_id)
+    }
+
+    pub fn is_banned(&self, steam_id: SteamId) -> bool {
+        self.banned.contains(&steam_id)
+    }
+
+    /// Returns the ban list and the allow list of the [`AccessPermission::InList`] permission,
+    /// to be persisted and restored with [`import_lists`](Self::import_lists).
+    pub fn export_lists(&self) -> AccessLists {
+        let sorted_ids = |list: &HashSet<SteamId>| {
+            let mut ids: Vec<u64> = list.iter().map(|steam_id| steam_id.raw()).collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        AccessLists {
+            banned: sorted_ids(&self.banned),
+            allowed: match &self.access_permission {
+                AccessPermission::InList(list) => Some(sorted_ids(list)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Replaces the ban list, and sets the access permission to [`AccessPermission::InList`] when the lists have an allow list.
+    /// Like [`set_access_permissions`](Self::set_access_permissions), it only applies to new connections.
+    pub fn import_lists(&mut self, lists: AccessLists) {
+        self.banned = lists.banned.into_iter().map(SteamId::from_raw).collect();
+        if let Some(allowed) = lists.allowed {
+            self.access_permission = AccessPermission::InList(allowed.into_iter().map(SteamId::from_raw).collect());
+        }
+    }
+
     /// Disconnects a client from the server.
     pub fn disconnect_client(&mut self, client_id: ClientId, server: &mut RenetServer, flush_last_packets: bool) {
         if let Some((_key, value)) = self.connections.remove_entry(&client_id) {
             let _ = value.close(NetConnectionEnd::AppGeneric, Some("Client was kicked"), flush_last_packets);
         }
+        self.remove_client_data(client_id);
+        server.remove_connection(client_id);
+    }
+
+    /// Disconnects a client from the server with an application disconnect code,
+    /// the client can read it with [`SteamClientTransport::app_disconnect_code`][crate::SteamClientTransport::app_disconnect_code].
+    ///
+    /// The code is sent as a last reliable message before closing the connection,
+    /// so the connection lingers until the pending packets are flushed.
+    /// Use [`DisconnectCodes`][crate::DisconnectCodes] to map the codes to your own reasons.
+    ///
+    /// # Panics
+    /// If the code is outside of [`APP_DISCONNECT_CODES`][crate::APP_DISCONNECT_CODES].
+    pub fn disconnect_client_with_code(&mut self, client_id: ClientId, server: &mut RenetServer, code: i32) {
+        assert!(
+            APP_DISCONNECT_CODES.contains(&code),
+            "disconnect code {code} is outside the application range"
+        );
+
+        if let Some((_key, value)) = self.connections.remove_entry(&client_id) {
+            let message = TransportMessage::Disconnect { code };
+            let send_flags = if self.enable_nagle { SendFlags::RELIABLE } else { SendFlags::RELIABLE_NO_NAGLE };
+            if let Err(e) = value.send_message(&message.to_bytes(), send_flags) {
+                log::error!("Failed to send disconnect code to client {client_id}: {e}");
+            }
+            let _ = value.close(NetConnectionEnd::AppGeneric, Some("Client was kicked"), true);
+        }
+        self.remove_client_data(client_id);
+        server.remove_connection(client_id);
+    }
+
+    /// Hands off a client to another host, for example from a lobby to a game server or to rebalance the load.
+    /// The client receives the steam id of the new host, see [`SteamClientTransport::redirect_target`][crate::SteamClientTransport::redirect_target],
+    /// and the application disconnect code as the reason, then it is disconnected like in [`Self::disconnect_client_with_code`].
+    ///
+    /// # Panics
+    /// If the code is outside of [`APP_DISCONNECT_CODES`][crate::APP_DISCONNECT_CODES].
+    pub fn redirect_client(&mut self, client_id: ClientId, server: &mut RenetServer, target: SteamId, code: i32) {
+        assert!(
+            APP_DISCONNECT_CODES.contains(&code),
+            "disconnect code {code} is outside the application range"
+        );
+
+        if let Some((_key, value)) = self.connections.remove_entry(&client_id) {
+            let message = TransportMessage::Redirect {
+                target: target.raw(),
+                code,
+            };
+            let send_flags = if self.enable_nagle { SendFlags::RELIABLE } else { SendFlags::RELIABLE_NO_NAGLE };
+            if let Err(e) = value.send_message(&message.to_bytes(), send_flags) {
+                log::error!("Failed to send redirect to client {client_id}: {e}");
+            }
+            let _ = value.close(NetConnectionEnd::AppGeneric, Some("Client was redirected"), true);
+        }
+        self.remove_client_data(client_id);
         server.remove_connection(client_id);
     }
 
@@ -105,96 +1200,796 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
                 Some("Client was kicked"),
                 flush_last_packets,
             );
+            self.remove_client_data(client_id);
             server.remove_connection(client_id);
         }
+        for (client_id, pending) in std::mem::take(&mut self.pending_connections) {
+            let _ = pending
+                .connection
+                .close(NetConnectionEnd::AppGeneric, Some("Client was kicked"), flush_last_packets);
+            self.remove_client_data(client_id);
+        }
+        self.accepted.clear();
+        for request in self.deferred_requests.drain(..) {
+            request.reject(NetConnectionEnd::AppGeneric, Some("Client was kicked"));
+        }
+    }
+
+    /// Ends a session created with [`SteamHostSession`][crate::SteamHostSession]: disconnects all clients,
+    /// leaves the lobby, clears the rich presence of the host and closes the listen socket.
+    pub fn end_host_session(mut self, server: &mut RenetServer, lobby_id: LobbyId) {
+        self.disconnect_all(server, true);
+        self.matchmaking.leave_lobby(lobby_id);
+        self.friends.clear_rich_presence();
+    }
+
+    /// Returns the events from the renet server and from the transport in the order they happened.
+    ///
+    /// Within an [`update`](Self::update), connections and disconnections from renet are ordered
+    /// relative to the rejections and failures reported by the transport. Renet events emitted
+    /// outside of the transport, like from local clients, come after the events already queued.
+    ///
+    /// Use this instead of [`RenetServer::get_event`], events taken from the server directly
+    /// will not be returned here.
+    pub fn poll_events<'a>(&'a mut self, server: &'a mut RenetServer) -> impl Iterator<Item = SteamServerEvent> + 'a {
+        self.events
+            .drain(..)
+            .chain(std::iter::from_fn(|| server.get_event().map(SteamServerEvent::Renet)))
     }
 
     /// Update server connections, and receive packets from the network.
     pub fn update(&mut self, server: &mut RenetServer) {
+        let relay_network_status = self.utils.relay_network_status();
+        if relay_network_status != self.relay_network_status {
+            self.relay_network_status = relay_network_status;
+            queue_event(
+                &mut self.events,
+                server,
+                SteamServerEvent::RelayNetworkStatusChanged {
+                    status: relay_network_status,
+                },
+            );
+        }
+
+        self.update_steam_status(server);
+
+        let persona_changes = std::mem::take(&mut *self.persona_changes.lock().unwrap());
+        for steam_id in persona_changes {
+            if let Some(&client_id) = self.client_ids.get(&steam_id) {
+                if self.personas.contains_key(&client_id) {
+                    let persona = self.fetch_persona(steam_id);
+                    self.personas.insert(client_id, persona);
+                }
+            }
+        }
+
+        self.stats.accepts_deferred = 0;
+        let mut accepts = 0;
+        // Requests deferred in the previous updates are answered first, in the order they arrived
+        for request in std::mem::take(&mut self.deferred_requests) {
+            self.handle_connection_request(server, request, &mut accepts);
+        }
+
         while let Some(event) = self.listen_socket.try_receive_event() {
             match event {
                 ListenSocketEvent::Connected(event) => {
                     if let Some(steam_id) = event.remote().steam_id() {
-                        server.add_connection(steam_id.raw());
-                        self.connections.insert(steam_id.raw(), event.take_connection());
+                        self.accepted.remove(&steam_id);
+                        let connection = event.take_connection();
+                        if self.local_addr.is_none() && matches!(self.listen_socket_kind, ListenSocketKind::Ip { .. }) {
+                            self.local_addr = self
+                                .sockets
+                                .get_connection_info(&connection)
+                                .ok()
+                                .and_then(|info| info.listen_socket())
+                                .and_then(listen_socket_address::<T>);
+                        }
+                        let client_id = match &mut self.client_id_generator {
+                            Some(generator) => generator(steam_id),
+                            None => steam_id.raw(),
+                        };
+                        if self.steam_ids.contains_key(&client_id) {
+                            let _ = connection.close(NetConnectionEnd::AppGeneric, Some("Duplicate client id"), false);
+                            let reason = RejectionReason::DuplicateClientId { client_id };
+                            let steam_id = Some(steam_id);
+                            queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+                            continue;
+                        }
+
+                        self.steam_ids.insert(client_id, steam_id);
+                        self.client_ids.insert(steam_id, client_id);
+                        if self.required_app_version.is_some() || self.max_spectators > 0 {
+                            let pending = PendingConnection {
+                                connection,
+                                connected_at: Instant::now(),
+                                version: None,
+                                spectator: None,
+                            };
+                            self.pending_connections.insert(client_id, pending);
+                        } else {
+                            self.add_client(server, client_id, steam_id, connection);
+                        }
                     }
                 }
                 ListenSocketEvent::Disconnected(event) => {
                     if let Some(steam_id) = event.remote().steam_id() {
-                        server.remove_connection(steam_id.raw());
-                        self.connections.remove(&steam_id.raw());
+                        self.accepted.remove(&steam_id);
+                    }
+                    if let Some(&client_id) = event.remote().steam_id().and_then(|steam_id| self.client_ids.get(&steam_id)) {
+                        self.pending_connections.remove(&client_id);
+                        server.remove_connection(client_id);
+                        self.connections.remove(&client_id);
+                        self.remove_client_data(client_id);
+                    }
+                }
+                ListenSocketEvent::Connecting(event) => self.handle_connection_request(server, event, &mut accepts),
+            }
+        }
+
+        self.check_pending_connections(server);
+        self.expire_sessions(server);
+        self.check_throughput_drops(server);
+        self.receive_messages(server);
+    }
+
+    // Accepts or rejects a connecting client, requests over the max_accepts_per_update are deferred to the next update
+    fn handle_connection_request(&mut self, server: &mut RenetServer, request: ConnectionRequest<T>, accepts: &mut usize) {
+        if matches!(self.max_accepts_per_update, Some(max_accepts) if *accepts >= max_accepts) {
+            self.deferred_requests.push_back(request);
+            self.stats.accepts_deferred += 1;
+            return;
+        }
+
+        if self.steam_status == SteamStatus::SteamOffline {
+            let steam_id = request.remote().steam_id();
+            request.reject(NetConnectionEnd::AppGeneric, Some("Steam offline"));
+            let reason = RejectionReason::SteamOffline;
+            queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+            return;
+        }
+
+        if server.connected_clients() + self.pending_connections.len() >= self.max_clients.saturating_add(self.max_spectators) {
+            let steam_id = request.remote().steam_id();
+            request.reject(NetConnectionEnd::AppGeneric, Some("Too many clients"));
+            let reason = RejectionReason::TooManyClients;
+            queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+            return;
+        }
+
+        let Some(steam_id) = request.remote().steam_id() else {
+            request.reject(NetConnectionEnd::AppGeneric, Some("Invalid steam id"));
+            let reason = RejectionReason::InvalidSteamId;
+            queue_event(
+                &mut self.events,
+                server,
+                SteamServerEvent::ConnectionRejected { steam_id: None, reason },
+            );
+            return;
+        };
+
+        if self.banned.contains(&steam_id) {
+            request.reject(NetConnectionEnd::AppGeneric, Some("Banned"));
+            let reason = RejectionReason::Banned;
+            let steam_id = Some(steam_id);
+            queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+            return;
+        }
+
+        let permitted = access_permitted(
+            &self.access_permission,
+            steam_id,
+            self.logged_on.load(Ordering::Relaxed),
+            |steam_id| self.friends.get_friend(steam_id).has_friend(FriendFlags::IMMEDIATE),
+            |lobby| {
+                // Steam has no owner for the lobbies the host is not a member of
+                let owner_known = self.matchmaking.lobby_owner(lobby).raw() != 0;
+                owner_known.then(|| self.matchmaking.lobby_members(lobby))
+            },
+        );
+
+        let permitted = match permitted {
+            Some(permitted) => permitted,
+            None => {
+                self.stats.access_checks_failed += 1;
+                match self.degraded_access_policy {
+                    DegradedAccessPolicy::FailClosed => {
+                        log::warn!("Steam failed to check the access permission, rejecting connection from {steam_id:?}");
+                        request.reject(NetConnectionEnd::AppGeneric, Some("Access check failed"));
+                        let reason = RejectionReason::AccessCheckFailed;
+                        let steam_id = Some(steam_id);
+                        queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+                        return;
+                    }
+                    DegradedAccessPolicy::FailOpen => {
+                        log::warn!("Steam failed to check the access permission, accepting connection from {steam_id:?}");
+                        queue_event(&mut self.events, server, SteamServerEvent::AccessCheckDegraded { steam_id });
+                        true
                     }
                 }
-                ListenSocketEvent::Connecting(event) => {
-                    if server.connected_clients() >= self.max_clients {
-                        event.reject(NetConnectionEnd::AppGeneric, Some("Too many clients"));
-                        continue;
+            }
+        };
+
+        if permitted {
+            *accepts += 1;
+            match request.accept() {
+                Ok(()) => {
+                    self.accepted.insert(steam_id, Instant::now());
+                }
+                Err(error) => {
+                    log::error!("Failed to accept connection from {steam_id:?}: {error}");
+                    queue_event(&mut self.events, server, SteamServerEvent::AcceptFailed { steam_id, error });
+                }
+            }
+        } else {
+            request.reject(NetConnectionEnd::AppGeneric, Some("Not allowed"));
+            let reason = RejectionReason::NotAllowed;
+            let steam_id = Some(steam_id);
+            queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+        }
+    }
+
+    fn add_client(&mut self, server: &mut RenetServer, client_id: ClientId, steam_id: SteamId, connection: NetConnection<T>) {
+        server.add_connection(client_id);
+        // Steam networking sockets always encrypt the connection
+        server.set_secure(client_id, true);
+        self.connections.insert(client_id, connection);
+        self.connected_at.insert(client_id, Instant::now());
+        self.record(client_id, RecordedEventKind::Connected);
+        let persona = self.fetch_persona(steam_id);
+        self.personas.insert(client_id, persona);
+    }
+
+    fn handle_unknown_client(&mut self, client_id: ClientId, server: &mut RenetServer) {
+        if self.host_client_id() == Some(client_id) {
+            return;
+        }
+
+        match self.unknown_client_policy {
+            UnknownClientPolicy::Log => {
+                if let Some(occurrences) = self.log_limiter.occurrence(client_id) {
+                    log::error!("Error while sending packet to client {client_id} ({occurrences} occurrences): connection not found");
+                }
+            }
+            UnknownClientPolicy::Remove => {
+                log::warn!("Removing client {client_id} from renet: connection not found");
+                server.remove_connection(client_id);
+            }
+            UnknownClientPolicy::Event => {
+                if self.unknown_clients.insert(client_id) {
+                    queue_event(&mut self.events, server, SteamServerEvent::UnknownClient { client_id });
+                }
+            }
+        }
+    }
+
+    fn expire_sessions(&mut self, server: &mut RenetServer) {
+        let Some(max_connection_lifetime) = self.max_connection_lifetime else {
+            return;
+        };
+
+        let expired: Vec<ClientId> = self
+            .connected_at
+            .iter()
+            .filter(|(_, connected_at)| connected_at.elapsed() >= max_connection_lifetime)
+            .map(|(client_id, _)| *client_id)
+            .collect();
+        for client_id in expired {
+            if let Some(connection) = self.connections.remove(&client_id) {
+                let message = TransportMessage::SessionExpired {
+                    redirect: self.session_redirect.clone(),
+                };
+                let send_flags = if self.enable_nagle { SendFlags::RELIABLE } else { SendFlags::RELIABLE_NO_NAGLE };
+                if let Err(e) = connection.send_message(&message.to_bytes(), send_flags) {
+                    log::error!("Failed to send session expiration to client {client_id}: {e}");
+                }
+                let _ = connection.close(NetConnectionEnd::AppGeneric, Some("Session expired"), true);
+            }
+            self.remove_client_data(client_id);
+            server.remove_connection(client_id);
+            queue_event(&mut self.events, server, SteamServerEvent::SessionExpired { client_id });
+        }
+    }
+
+    fn check_throughput_drops(&mut self, server: &mut RenetServer) {
+        let Some(config) = self.throughput_drop else {
+            return;
+        };
+
+        let now = Instant::now();
+        for (&client_id, connection) in self.connections.iter() {
+            let Ok((info, _)) = self.sockets.get_realtime_connection_status(connection, 0) else {
+                continue;
+            };
+
+            let kbps = info.send_rate_bytes_per_sec() as f32 * 8.0 / 1000.0;
+            let detector = self.throughput_drop_detectors.entry(client_id).or_default();
+            if let Some((from_kbps, to_kbps)) = detector.sample(&config, now, kbps) {
+                queue_event(
+                    &mut self.events,
+                    server,
+                    SteamServerEvent::ThroughputDrop {
+                        client_id,
+                        from_kbps,
+                        to_kbps,
+                    },
+                );
+            }
+        }
+    }
+
+    fn check_pending_connections(&mut self, server: &mut RenetServer) {
+        let client_ids: Vec<ClientId> = self.pending_connections.keys().copied().collect();
+        for client_id in client_ids {
+            let pending = self.pending_connections.get_mut(&client_id).unwrap();
+            // Renet packets received before the version and class are dropped, renet resends the reliable ones
+            if let Ok(messages) = pending.connection.receive_messages(MAX_MESSAGE_BATCH_SIZE) {
+                for message in messages.iter() {
+                    match TransportMessage::from_bytes(message.data()) {
+                        Some(TransportMessage::ConnectionClass { spectator }) => pending.spectator = Some(spectator),
+                        message => {
+                            // Spectators send their class before anything else, clients without a class are players
+                            pending.spectator.get_or_insert(false);
+                            if let Some(TransportMessage::AppVersion { version }) = message {
+                                pending.version = Some(version);
+                            }
+                        }
                     }
+                }
+            }
+
+            let timed_out = pending.connected_at.elapsed() >= APP_VERSION_TIMEOUT;
+            if let Some(required_app_version) = self.required_app_version {
+                let rejected_version = match pending.version {
+                    Some(version) if version == required_app_version => None,
+                    Some(version) => Some(Some(version)),
+                    None if timed_out => Some(None),
+                    None => continue,
+                };
+
+                if let Some(rejected_version) = rejected_version {
+                    let reason = RejectionReason::AppVersionMismatch { rejected_version };
+                    self.reject_pending(server, client_id, "Version mismatch", reason);
+                    continue;
+                }
+            }
+
+            // The class is sent before the version, it is only waited for when the version is not required
+            if self.max_spectators > 0 && pending.spectator.is_none() && !timed_out {
+                continue;
+            }
+
+            let spectator = pending.spectator.unwrap_or(false);
+            if self.max_spectators > 0 {
+                if spectator && self.spectators.len() >= self.max_spectators {
+                    self.reject_pending(server, client_id, "Too many spectators", RejectionReason::TooManySpectators);
+                    continue;
+                }
+                if !spectator && self.player_count(server) >= self.max_clients {
+                    self.reject_pending(server, client_id, "Too many clients", RejectionReason::TooManyClients);
+                    continue;
+                }
+            }
 
-                    let Some(steam_id) = event.remote().steam_id() else {
-                        event.reject(NetConnectionEnd::AppGeneric, Some("Invalid steam id"));
-                        continue;
+            let pending = self.pending_connections.remove(&client_id).unwrap();
+            let steam_id = self.steam_ids[&client_id];
+            self.add_client(server, client_id, steam_id, pending.connection);
+            if spectator {
+                self.spectators.insert(client_id);
+            }
+        }
+    }
+
+    fn reject_pending(&mut self, server: &mut RenetServer, client_id: ClientId, debug_reason: &str, reason: RejectionReason) {
+        let pending = self.pending_connections.remove(&client_id).unwrap();
+        let _ = pending.connection.close(NetConnectionEnd::AppGeneric, Some(debug_reason), false);
+        let steam_id = self.steam_ids.get(&client_id).copied();
+        self.remove_client_data(client_id);
+        queue_event(&mut self.events, server, SteamServerEvent::ConnectionRejected { steam_id, reason });
+    }
+
+    fn receive_messages(&mut self, server: &mut RenetServer) {
+        // Clients are visited in a stable order, starting after the last client served when the budget was exhausted
+        self.receive_order.clear();
+        self.receive_order.extend(self.connections.keys());
+        self.receive_order.sort_unstable();
+        self.next_receive_index = self.next_receive_index.checked_rem(self.receive_order.len()).unwrap_or(0);
+        self.receive_order.rotate_left(self.next_receive_index);
+
+        let mut budget = self.max_messages_per_update.unwrap_or(usize::MAX);
+        let mut messages_received = 0;
+        let mut bytes_received = 0;
+        let mut served_clients = 0;
+        for client_id in self.receive_order.iter() {
+            if budget == 0 {
+                break;
+            }
+            served_clients += 1;
+
+            let connection = self.connections.get_mut(client_id).unwrap();
+            let max_messages = match self.max_receive_queue_messages {
+                Some(max_queue) => MAX_MESSAGE_BATCH_SIZE.max(max_queue),
+                None => MAX_MESSAGE_BATCH_SIZE,
+            };
+            let max_messages = budget.min(max_messages);
+            // TODO this allocates on the side of steamworks.rs and should be avoided, PR needed
+            if let Ok(messages) = connection.receive_messages(max_messages) {
+                budget -= messages.len();
+                messages_received += messages.len();
+                bytes_received += messages.iter().map(|message| message.data().len() as u64).sum::<u64>();
+                if matches!(self.max_receive_queue_messages, Some(max_queue) if messages.len() >= max_queue) {
+                    self.stats.receive_queue_full += 1;
+                    let client_id = *client_id;
+                    queue_event(&mut self.events, server, SteamServerEvent::ReceiveQueueFull { client_id });
+                }
+                if self.muted_clients.contains(client_id) {
+                    continue;
+                }
+                for message in messages.iter() {
+                    let decompressed;
+                    let data = match message::is_compressed(message.data()) {
+                        true => match message::decompress_packet(message.data()) {
+                            Some(data) => {
+                                decompressed = data;
+                                decompressed.as_slice()
+                            }
+                            None => continue,
+                        },
+                        false => message.data(),
+                    };
+                    let packets = match TransportMessage::is_transport_message(data) {
+                        // Clients only send transport messages with coalesced packets
+                        true => match message::split_batch(data) {
+                            Some(packets) => packets,
+                            None => continue,
+                        },
+                        false => vec![data],
                     };
 
-                    let permitted = match &self.access_permission {
-                        AccessPermission::Public => true,
-                        AccessPermission::Private => false,
-                        AccessPermission::FriendsOnly => {
-                            let friend = self.friends.get_friend(steam_id);
-                            friend.has_friend(FriendFlags::IMMEDIATE)
+                    for packet in packets {
+                        if let Some(inspector) = &mut self.packet_inspector {
+                            inspector(ReceivedMessage {
+                                client_id: *client_id,
+                                data: packet,
+                            });
+                        }
+
+                        if let Some((started_at, recording)) = &mut self.recording {
+                            recording.push(started_at.elapsed(), *client_id, RecordedEventKind::Packet(packet.to_vec()));
                         }
-                        AccessPermission::InList(list) => list.contains(&steam_id),
-                        AccessPermission::InLobby(lobby) => {
-                            let users_in_lobby = self.matchmaking.lobby_members(*lobby);
-                            users_in_lobby.contains(&steam_id)
+
+                        if self.buffer_received {
+                            self.received.push((*client_id, Bytes::copy_from_slice(packet)));
+                            continue;
                         }
-                    };
 
-                    if permitted {
-                        if let Err(e) = event.accept() {
-                            log::error!("Failed to accept connection from {steam_id:?}: {e}");
+                        match server.process_packet_from(packet, *client_id) {
+                            Ok(()) => {}
+                            Err(ProcessError::Duplicate) => self.stats.duplicate_packets += 1,
+                            Err(ProcessError::OutOfWindow) => self.stats.out_of_window_packets += 1,
+                            Err(e) => {
+                                if matches!(e, ProcessError::Malformed(_)) {
+                                    self.stats.malformed_packets += 1;
+                                }
+                                if let Some(occurrences) = self.log_limiter.occurrence(*client_id) {
+                                    log::error!("Error while processing payload for {client_id} ({occurrences} occurrences): {e}");
+                                }
+                            }
                         }
-                    } else {
-                        event.reject(NetConnectionEnd::AppGeneric, Some("Not allowed"));
                     }
                 }
+
+                if let Some(callback) = &mut self.per_client_callback {
+                    callback(*client_id, server);
+                }
             }
         }
 
-        for (client_id, connection) in self.connections.iter_mut() {
-            // TODO this allocates on the side of steamworks.rs and should be avoided, PR needed
-            if let Ok(messages) = connection.receive_messages(MAX_MESSAGE_BATCH_SIZE) {
-                messages.iter().for_each(|message| {
-                    if let Err(e) = server.process_packet_from(message.data(), *client_id) {
-                        log::error!("Error while processing payload for {}: {}", client_id, e);
-                    };
-                });
+        let receive_budget_exhausted = self.max_messages_per_update.is_some() && budget == 0;
+        self.next_receive_index = match receive_budget_exhausted {
+            true => self.next_receive_index + served_clients,
+            false => 0,
+        };
+        self.stats.messages_received = messages_received;
+        self.stats.bytes_received += bytes_received;
+        self.throughput.record(Instant::now(), 0, bytes_received);
+        self.stats.receive_budget_exhausted = receive_budget_exhausted;
+        if receive_budget_exhausted {
+            self.stats.receive_budget_exhausted_count += 1;
+        }
+    }
+
+    fn update_steam_status(&mut self, server: &mut RenetServer) {
+        let steam_status = if self.logged_on.load(Ordering::Relaxed) { SteamStatus::Online } else { SteamStatus::SteamOffline };
+        if steam_status == self.steam_status {
+            return;
+        }
+
+        self.steam_status = steam_status;
+        queue_event(
+            &mut self.events,
+            server,
+            SteamServerEvent::SteamStatusChanged { status: steam_status },
+        );
+
+        if steam_status == SteamStatus::Online && self.recreate_listen_socket {
+            let listen_socket = match &self.listen_socket_kind {
+                ListenSocketKind::P2p { options } => self.sockets.create_listen_socket_p2p(0, options.clone()),
+                ListenSocketKind::Ip { addr, options } => self.sockets.create_listen_socket_ip(*addr, options.clone()),
+            };
+            match listen_socket {
+                Ok(listen_socket) => {
+                    self.disconnect_all(server, false);
+                    self.listen_socket = listen_socket;
+                    // An ephemeral port is not kept when recreating the listen socket
+                    if matches!(self.listen_socket_kind, ListenSocketKind::Ip { addr, .. } if addr.port() == 0) {
+                        self.local_addr = None;
+                    }
+                }
+                Err(_) => log::error!("Failed to recreate the listen socket, keeping the previous one"),
             }
         }
     }
 
     /// Send packets to connected clients.
     pub fn send_packets(&mut self, server: &mut RenetServer) {
-        'clients: for client_id in server.clients_id() {
-            let Some(connection) = self.connections.get(&client_id) else {
-                log::error!("Error while sending packet: connection not found");
-                continue;
+        let mut budget = self.max_sends_per_update.unwrap_or(usize::MAX);
+        // Packets that failed in the previous call are sent first, they are only retried once
+        for (client_id, packet) in std::mem::take(&mut self.send_retries) {
+            if self.connections.contains_key(&client_id) {
+                self.queue_packet(client_id, packet, false);
+                budget = budget.saturating_sub(1);
+            }
+        }
+
+        // Then the packets over the budget of the previous call, in the order they were generated
+        while budget > 0 {
+            let Some((client_id, packet, retry)) = self.deferred_sends.pop_front() else {
+                break;
             };
-            let packets = server.get_packets_to_send(client_id).unwrap();
+            if self.connections.contains_key(&client_id) {
+                self.queue_packet(client_id, packet, retry);
+                budget -= 1;
+            }
+        }
+
+        self.unknown_clients.retain(|&client_id| server.is_connected(client_id));
+        let mut clients_id = server.clients_id();
+        if self.send_order == SendOrder::RoundRobin && !clients_id.is_empty() {
+            clients_id.sort_unstable();
+            self.next_send_index %= clients_id.len();
+            clients_id.rotate_left(self.next_send_index);
+            self.next_send_index += 1;
+        }
+        // Clients left out by the budget of the previous call are served first
+        let deferred_clients = std::mem::take(&mut self.deferred_clients);
+        if !deferred_clients.is_empty() {
+            clients_id.sort_by_key(|client_id| !deferred_clients.contains(client_id));
+        }
+
+        for client_id in clients_id {
+            if budget == 0 {
+                self.deferred_clients.insert(client_id);
+                continue;
+            }
+            if !self.connections.contains_key(&client_id) {
+                self.handle_unknown_client(client_id, server);
+                continue;
+            }
+            let mut packets = server.get_packets_to_send(client_id).unwrap();
+            if let Some(max_size) = self.coalesce_packets {
+                packets = message::coalesce_packets(packets, max_size);
+            }
 
             for packet in packets {
-                let mut message = self.utils.allocate_message(0);
-                message.set_connection(connection);
-                message.set_send_flags(SendFlags::UNRELIABLE_NO_NAGLE);
-                if let Err(e) = message.set_data(packet) {
-                    log::error!("Failed to send packet to client {client_id}: {e}");
-                    continue 'clients;
+                let retry = self.send_retry_buffer_size > 0 && is_reliable_packet(&packet);
+                let packet = if self.compress_packets { message::compress_packet(packet) } else { packet };
+                if budget == 0 {
+                    self.deferred_sends.push_back((client_id, packet, retry));
+                    continue;
+                }
+                if !self.queue_packet(client_id, packet, retry) {
+                    break;
                 }
-                self.messages.push(message);
+                budget -= 1;
+            }
+        }
+        self.stats.sends_deferred = self.deferred_sends.len();
+        self.stats.clients_deferred = self.deferred_clients.len();
+
+        let bytes_sent = self.messages.iter().map(|message| message.data().len() as u64).sum();
+        self.stats.bytes_sent += bytes_sent;
+        self.throughput.record(Instant::now(), bytes_sent, 0);
+        if self.messages.is_empty() {
+            return;
+        }
+
+        let results = self.listen_socket.send_messages(self.messages.drain(..));
+        for (result, (client_id, packet)) in results.into_iter().zip(self.queued_packets.drain(..)) {
+            let (Err(SteamError::LimitExceeded | SteamError::Busy), Some(packet)) = (result, packet) else {
+                continue;
+            };
+
+            if self.send_retries.len() < self.send_retry_buffer_size {
+                self.send_retries.push((client_id, packet));
+                self.stats.send_retries += 1;
+            } else {
+                self.stats.send_retries_dropped += 1;
+            }
+        }
+    }
+
+    // Returns false if the packet could not be set in a steam message
+    fn queue_packet(&mut self, client_id: ClientId, packet: Vec<u8>, retry: bool) -> bool {
+        let retry_packet = retry.then(|| packet.clone());
+        let mut message = self.utils.allocate_message(0);
+        message.set_connection(&self.connections[&client_id]);
+        message.set_send_flags(if self.enable_nagle { SendFlags::UNRELIABLE } else { SendFlags::UNRELIABLE_NO_NAGLE });
+        if let Err(e) = message.set_data(packet) {
+            if let Some(occurrences) = self.log_limiter.occurrence(client_id) {
+                log::error!("Failed to send packet to client {client_id} ({occurrences} occurrences): {e}");
             }
+            return false;
         }
-        if !self.messages.is_empty() {
-            self.listen_socket.send_messages(self.messages.drain(..));
+
+        self.messages.push(message);
+        self.queued_packets.push((client_id, retry_packet));
+        true
+    }
+}
+
+impl<T> Drop for SteamServerTransport<T> {
+    fn drop(&mut self) {
+        for callback in self.steam_callbacks.iter() {
+            callback.disconnect();
         }
     }
 }
+
+// Steamworks does not expose the receive buffer config values, so it is set through the raw api
+fn set_receive_queue_limit(config: &SteamServerConfig) {
+    let Some(max_queue) = config.max_receive_queue_messages else {
+        return;
+    };
+
+    let max_queue = max_queue.min(i32::MAX as usize) as i32;
+    // SAFETY: the networking utils interface is valid while steam is initialized, the server requires a steam client
+    let set = unsafe {
+        let utils = steamworks::sys::SteamAPI_SteamNetworkingUtils_SteamAPI_v004();
+        steamworks::sys::SteamAPI_ISteamNetworkingUtils_SetGlobalConfigValueInt32(
+            utils,
+            steamworks::sys::ESteamNetworkingConfigValue::k_ESteamNetworkingConfig_RecvBufferMessages,
+            max_queue,
+        )
+    };
+    if !set {
+        log::error!("Failed to set the steam receive queue limit to {max_queue} messages");
+    }
+}
+
+// Reads the address a listen socket is bound to, steamworks-rs has no wrapper for GetListenSocketAddress
+fn listen_socket_address<T: 'static>(listen_socket: steamworks::sys::HSteamListenSocket) -> Option<SocketAddr> {
+    use steamworks::sys;
+
+    // SAFETY: the interfaces are available once steam is initialized, the address is plain data written by steam
+    let mut address: sys::SteamNetworkingIPAddr = unsafe { std::mem::zeroed() };
+    let found = unsafe {
+        let sockets = match TypeId::of::<T>() == TypeId::of::<ServerManager>() {
+            true => sys::SteamAPI_SteamGameServerNetworkingSockets_SteamAPI_v012(),
+            false => sys::SteamAPI_SteamNetworkingSockets_SteamAPI_v012(),
+        };
+        sys::SteamAPI_ISteamNetworkingSockets_GetListenSocketAddress(sockets, listen_socket, &mut address)
+    };
+    if !found {
+        return None;
+    }
+
+    // Steam stores ipv4 addresses as ipv4-mapped ipv6 addresses
+    let ipv6 = Ipv6Addr::from(unsafe { address.__bindgen_anon_1.m_ipv6 });
+    let ip = match ipv6.to_ipv4_mapped() {
+        Some(ipv4) => IpAddr::V4(ipv4),
+        None => IpAddr::V6(ipv6),
+    };
+    Some(SocketAddr::new(ip, address.m_port))
+}
+
+// Added to the score of relayed routes, a direct route is selected unless its ping is this much higher
+const RELAY_ROUTE_PENALTY_MS: i32 = 100;
+
+fn listen_socket_options(config: &SteamServerConfig, mut options: Vec<NetworkingConfigEntry>) -> Vec<NetworkingConfigEntry> {
+    // Connections accepted by the listen socket inherit its config values
+    if config.prefer_direct_connections {
+        options.push(NetworkingConfigEntry::new_int32(
+            NetworkingConfigValue::P2PTransportICEEnable,
+            steamworks::sys::k_nSteamNetworkingConfig_P2P_Transport_ICE_Enable_All,
+        ));
+        options.push(NetworkingConfigEntry::new_int32(
+            NetworkingConfigValue::P2PTransportSDRPenalty,
+            RELAY_ROUTE_PENALTY_MS,
+        ));
+    }
+    if !config.enable_nagle {
+        options.push(NetworkingConfigEntry::new_int32(NetworkingConfigValue::NagleTime, 0));
+    }
+    if let Some(connection_timeout) = config.connection_timeout {
+        let timeout_ms = connection_timeout.as_millis().min(i32::MAX as u128) as i32;
+        options.push(NetworkingConfigEntry::new_int32(
+            NetworkingConfigValue::TimeoutConnected,
+            timeout_ms,
+        ));
+    }
+
+    options
+}
+
+// Returns whether the permission lets the user connect, None when steam has no data to answer the query.
+// The friends and lobby data is only available while logged on, lobby_members is None without data for the lobby.
+fn access_permitted(
+    access_permission: &AccessPermission,
+    steam_id: SteamId,
+    logged_on: bool,
+    is_friend: impl FnOnce(SteamId) -> bool,
+    lobby_members: impl FnOnce(LobbyId) -> Option<Vec<SteamId>>,
+) -> Option<bool> {
+    match access_permission {
+        AccessPermission::Public => Some(true),
+        AccessPermission::Private => Some(false),
+        AccessPermission::FriendsOnly => logged_on.then(|| is_friend(steam_id)),
+        AccessPermission::InList(list) => Some(list.contains(&steam_id)),
+        AccessPermission::InLobby(lobby) => logged_on.then(|| lobby_members(*lobby))?.map(|members| members.contains(&steam_id)),
+    }
+}
+
+// Renet packets start with the packet type, 0 is SmallReliable and 2 is ReliableSlice
+fn is_reliable_packet(packet: &[u8]) -> bool {
+    if message::is_batch(packet) {
+        return message::split_batch(packet).is_some_and(|packets| packets.iter().any(|packet| is_reliable_packet(packet)));
+    }
+
+    matches!(packet.first(), Some(0 | 2))
+}
+
+// Moves the pending renet events before the transport event, so they keep the order they happened
+fn queue_event(events: &mut VecDeque<SteamServerEvent>, server: &mut RenetServer, event: SteamServerEvent) {
+    while let Some(server_event) = server.get_event() {
+        events.push_back(SteamServerEvent::Renet(server_event));
+    }
+    events.push_back(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_permission_queries() {
+        let host = SteamId::from_raw(1);
+        let user = SteamId::from_raw(2);
+        let lobby = LobbyId::from_raw(3);
+
+        // A host without friends rejects the user instead of failing the query
+        assert_eq!(
+            access_permitted(&AccessPermission::FriendsOnly, user, true, |_| false, |_| None),
+            Some(false)
+        );
+        assert_eq!(
+            access_permitted(&AccessPermission::FriendsOnly, user, true, |_| true, |_| None),
+            Some(true)
+        );
+        assert_eq!(
+            access_permitted(&AccessPermission::FriendsOnly, user, false, |_| true, |_| None),
+            None
+        );
+
+        let in_lobby = AccessPermission::InLobby(lobby);
+        assert_eq!(
+            access_permitted(&in_lobby, user, true, |_| false, |_| Some(vec![host])),
+            Some(false)
+        );
+        assert_eq!(
+            access_permitted(&in_lobby, user, true, |_| false, |_| Some(vec![host, user])),
+            Some(true)
+        );
+        assert_eq!(access_permitted(&in_lobby, user, true, |_| false, |_| None), None);
+        assert_eq!(access_permitted(&in_lobby, user, false, |_| false, |_| Some(vec![user])), None);
+    }
+}
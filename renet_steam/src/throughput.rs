@@ -0,0 +1,185 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Bytes per second sent and received by the transport, see [`SteamServerTransport::throughput`][crate::SteamServerTransport::throughput].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Throughput {
+    /// Bytes sent per second
+    pub send_bps: f64,
+    /// Bytes received per second
+    pub recv_bps: f64,
+}
+
+/// Detection of sharp drops of the rate steam can send to a client,
+/// see [`SteamServerConfig::throughput_drop`][crate::SteamServerConfig::throughput_drop].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThroughputDropConfig {
+    /// Fraction of the previous rate under which the rate is considered dropped, 0.5 reports drops of half the rate.
+    pub drop_ratio: f32,
+    /// Time the rate must stay under the threshold before the drop is reported, short dips are ignored.
+    pub debounce: Duration,
+}
+
+impl Default for ThroughputDropConfig {
+    fn default() -> Self {
+        Self {
+            drop_ratio: 0.5,
+            debounce: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Sample {
+    time: Instant,
+    sent: u64,
+    received: u64,
+}
+
+// Bytes sent and received during a sliding window of time
+#[derive(Debug)]
+pub(crate) struct ThroughputWindow {
+    window: Duration,
+    samples: VecDeque<Sample>,
+}
+
+impl ThroughputWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    pub fn record(&mut self, time: Instant, sent: u64, received: u64) {
+        while let Some(sample) = self.samples.front() {
+            if time.duration_since(sample.time) < self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        if sent > 0 || received > 0 {
+            self.samples.push_back(Sample { time, sent, received });
+        }
+    }
+
+    pub fn throughput(&self, now: Instant) -> Throughput {
+        let window_secs = self.window.as_secs_f64();
+        if window_secs == 0.0 {
+            return Throughput::default();
+        }
+
+        let (sent, received) = self
+            .samples
+            .iter()
+            .filter(|sample| now.duration_since(sample.time) < self.window)
+            .fold((0, 0), |(sent, received), sample| (sent + sample.sent, received + sample.received));
+
+        Throughput {
+            send_bps: sent as f64 / window_secs,
+            recv_bps: received as f64 / window_secs,
+        }
+    }
+}
+
+// Rate of a client compared to the last stable rate, the reference follows the rate while it does not drop
+#[derive(Debug, Default)]
+pub(crate) struct ThroughputDropDetector {
+    reference_kbps: Option<f32>,
+    dropped_since: Option<Instant>,
+}
+
+impl ThroughputDropDetector {
+    // Returns the reference and current rate when the rate stayed under the threshold during the debounce
+    pub fn sample(&mut self, config: &ThroughputDropConfig, now: Instant, kbps: f32) -> Option<(f32, f32)> {
+        let Some(reference_kbps) = self.reference_kbps else {
+            self.reference_kbps = Some(kbps);
+            return None;
+        };
+
+        if kbps >= reference_kbps * config.drop_ratio {
+            self.reference_kbps = Some(kbps);
+            self.dropped_since = None;
+            return None;
+        }
+
+        let dropped_since = *self.dropped_since.get_or_insert(now);
+        if now.duration_since(dropped_since) < config.debounce {
+            return None;
+        }
+
+        // The dropped rate is the new reference, so the drop is only reported once
+        self.reference_kbps = Some(kbps);
+        self.dropped_since = None;
+        Some((reference_kbps, kbps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput_over_window() {
+        let start = Instant::now();
+        let mut window = ThroughputWindow::new(Duration::from_secs(1));
+
+        window.record(start, 1000, 500);
+        window.record(start + Duration::from_millis(500), 1000, 0);
+        let throughput = window.throughput(start + Duration::from_millis(500));
+        assert_eq!(throughput.send_bps, 2000.0);
+        assert_eq!(throughput.recv_bps, 500.0);
+
+        // The first sample left the window
+        let throughput = window.throughput(start + Duration::from_millis(1200));
+        assert_eq!(throughput.send_bps, 1000.0);
+        assert_eq!(throughput.recv_bps, 0.0);
+
+        window.record(start + Duration::from_secs(3), 0, 0);
+        assert_eq!(window.throughput(start + Duration::from_secs(3)), Throughput::default());
+    }
+
+    #[test]
+    fn throughput_drop() {
+        let start = Instant::now();
+        let config = ThroughputDropConfig {
+            drop_ratio: 0.5,
+            debounce: Duration::from_secs(1),
+        };
+        let mut detector = ThroughputDropDetector::default();
+
+        assert_eq!(detector.sample(&config, start, 1000.0), None);
+        // Short dip under the threshold
+        assert_eq!(detector.sample(&config, start + Duration::from_millis(100), 200.0), None);
+        assert_eq!(detector.sample(&config, start + Duration::from_millis(200), 900.0), None);
+
+        assert_eq!(detector.sample(&config, start + Duration::from_secs(1), 300.0), None);
+        assert_eq!(detector.sample(&config, start + Duration::from_millis(1500), 250.0), None);
+        assert_eq!(
+            detector.sample(&config, start + Duration::from_secs(2), 200.0),
+            Some((900.0, 200.0))
+        );
+
+        // Reported once, the dropped rate is the new reference
+        assert_eq!(detector.sample(&config, start + Duration::from_secs(4), 200.0), None);
+    }
+
+    #[test]
+    fn smaller_window_scales_rate() {
+        let start = Instant::now();
+        let mut window = ThroughputWindow::new(Duration::from_secs(1));
+        window.set_window(Duration::from_millis(250));
+
+        window.record(start, 100, 100);
+        let throughput = window.throughput(start);
+        assert_eq!(throughput.send_bps, 400.0);
+        assert_eq!(throughput.recv_bps, 400.0);
+    }
+}